@@ -2,71 +2,100 @@ use std::time::Duration;
 
 use rand::Rng;
 
-use bevy::{prelude::*, render::camera::OrthographicProjection, sprite::collide_aabb::collide};
+use bevy::{
+    app::AppExit, prelude::*, render::camera::OrthographicProjection,
+    sprite::collide_aabb::collide,
+};
 
 use bevy_level_plan::{
-    level_plan_system, Conditional, Cycle, LevelPlan, LevelPlanElement, Sequence, SetComponent,
-    While, Nop, LevelContext,
+    app_state_system, level_plan_system, AppState, Conditional, Director, EnterAppState, FnElement,
+    LevelPlan, LevelPlanElement, OnActivate, Sequence, SetComponent, While, Nop, LevelContext,
+    StepInfo, EnterRegion, Region, Timeout, TrackedTransforms, Tracker,
 };
 
-/// LevelPlan related stuff
+// LevelPlan related stuff
 
-fn make_level_plan(level_length: f32) -> LevelPlan<ExampleLevelContext> {
+fn make_level_plan(
+    bounds: Rect<f32>,
+    app_state: &AppState<GameState>,
+) -> LevelPlan<ExampleLevelContext> {
+    let level_length = bounds.top - bounds.bottom;
+    let boss_arena = Rect {
+        bottom: bounds.top - 768.0,
+        ..bounds
+    };
     LevelPlan::<ExampleLevelContext>::new(
         Sequence::default()
             .push(ForDistance::new(
                 level_length - 1800.0,
-                Cycle::new(
-                    Sequence::default()
-                        .push(ForDistance::new(
-                            500.0,
-                            SetComponent::new(DiverSpawner::default()),
-                        ))
-                        .push(ForDistance::new(
-                            500.0,
-                            SetComponent::new(SwooperSpawner::default()),
-                        )),
-                ),
+                Director::new(1.0, 3.0, |context: &ExampleLevelContext| {
+                    4u32.saturating_sub(context.player_health) as f32
+                        + context.enemies_alive as f32 * 0.25
+                })
+                .encounter("divers", 1.0, Timeout::after(
+                    Duration::from_secs(4),
+                    SetComponent::new(DiverSpawner::default()),
+                ))
+                .encounter("swoopers", 2.0, Timeout::after(
+                    Duration::from_secs(2),
+                    SetComponent::new(SwooperSpawner::default()),
+                ))
+                .peak_for(Duration::from_secs(3))
+                .relax_with(Nop),
             ))
-            .push(ForDistance::new(
-                1000.0,
-                Nop
+            .push(EnterRegion::new(
+                Region::Rect(boss_arena),
+                Tracker::marker::<Player>(),
+                Nop,
             ))
             .push(Conditional::<ExampleLevelContext>::new(
                 move |context| context.player_health < 4,
-                SpawnPowerups,
+                OnActivate::new(|_level, commands, _context| {
+                    for _ in 0..3 {
+                        commands.spawn((Powerup,));
+                    }
+                }),
             ))
             .push(While::<ExampleLevelContext>::new(
                 |context| context.boss_spawned,
-                SpawnBoss,
+                FnElement::new()
+                    .named("SpawnBoss")
+                    .on_activate(|_, _level, commands, _context| {
+                        commands.spawn((Boss,));
+                    }),
             ))
-            .push(YouWin),
+            .push(EnterAppState::new(app_state, GameState::Victory)),
     )
 }
 
 struct ExampleLevelContext {
+    tracked: TrackedTransforms,
     player_loc: Vec3,
     player_health: u32,
+    enemies_alive: usize,
     boss_spawned: bool,
 }
 impl LevelContext for ExampleLevelContext {
     fn build(world: &World, _resources: &Resources) -> Self {
-        let mut player_loc = Vec3::zero();
-        let mut player_health = 4;
-        if let Some((_, transform, health)) = world
+        let (player_loc, player_health) = world
             .query::<(&Player, &Transform, &Health)>()
             .iter()
             .next()
-        {
-            player_loc = transform.translation();
-            player_health = health.0;
-        }
+            .map_or((Vec3::zero(), 4), |(_, transform, health)| {
+                (transform.translation(), health.0)
+            });
         Self {
+            tracked: TrackedTransforms::default().with_marker::<Player>(world),
             player_loc,
             player_health,
+            enemies_alive: world.query::<&Enemy>().iter().count(),
             boss_spawned: world.query::<&Boss>().iter().count() > 0,
         }
     }
+
+    fn tracked_transforms(&self) -> Option<&TrackedTransforms> {
+        Some(&self.tracked)
+    }
 }
 
 pub struct ForDistance {
@@ -84,9 +113,9 @@ impl ForDistance {
     }
 }
 impl LevelPlanElement<ExampleLevelContext> for ForDistance {
-    fn step(&mut self, level: Entity, commands: &mut Commands, context: &mut ExampleLevelContext) -> bool {
+    fn step(&mut self, level: Entity, commands: &mut Commands, context: &mut ExampleLevelContext, info: &StepInfo) -> bool {
         if context.player_loc.y() < self.start + self.length {
-            self.element.step(level, commands, context)
+            self.element.step(level, commands, context, info)
         } else {
             false
         }
@@ -100,38 +129,35 @@ impl LevelPlanElement<ExampleLevelContext> for ForDistance {
     fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut ExampleLevelContext) {
         self.element.deactivate(level, commands, context);
     }
-}
 
-struct SpawnPowerups;
-impl LevelPlanElement<ExampleLevelContext> for SpawnPowerups {
-    fn step(
-        &mut self,
-        _level: Entity,
-        _commands: &mut Commands,
-        _context: &mut ExampleLevelContext,
-    ) -> bool {
-        false
+    fn reset(&mut self) {
+        self.start = f32::MAX;
+        self.element.reset();
     }
 
-    fn activate(&mut self, _level: Entity, commands: &mut Commands, _context: &mut ExampleLevelContext) {
-        for _ in 0..3 {
-            commands.spawn((Powerup,));
-        }
+    fn children(&self) -> Vec<&dyn LevelPlanElement<ExampleLevelContext>> {
+        vec![self.element.as_ref()]
     }
-}
 
-struct SpawnBoss;
-impl<T> LevelPlanElement<T> for SpawnBoss {
-    fn activate(&mut self, _level: Entity, commands: &mut Commands, _context: &mut T) {
-        commands.spawn((Boss,));
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<ExampleLevelContext>>> {
+        vec![&mut self.element]
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        vec![0]
     }
 }
 
-struct YouWin;
-impl<T> LevelPlanElement<T> for YouWin {
-    fn activate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {
+#[derive(Clone, PartialEq)]
+enum GameState {
+    Playing,
+    Victory,
+}
+
+fn victory(app_state: Res<AppState<GameState>>, mut exit: ResMut<Events<AppExit>>) {
+    if app_state.is(&GameState::Victory) {
         println!("You win!");
-        std::process::exit(0);
+        exit.send(AppExit);
     }
 }
 
@@ -211,12 +237,15 @@ fn swooper_spawner(
     }
 }
 
-/// General game stuff
+// General game stuff
 
 fn main() {
     App::build()
         .add_default_plugins()
+        .add_resource(AppState::new(GameState::Playing))
         .add_system(level_plan_system::<ExampleLevelContext>.thread_local_system())
+        .add_system(app_state_system::<GameState>.thread_local_system())
+        .add_system(victory.system())
         .add_system(player_controls.system())
         .add_system(movement.system())
         .add_system(diver_spawner.system())
@@ -244,7 +273,11 @@ struct Powerup;
 #[derive(Clone)]
 struct LevelBounds(Rect<f32>);
 
-fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
+fn setup(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    app_state: Res<AppState<GameState>>,
+) {
     let bounds = Rect {
         left: -500.0,
         right: 500.0,
@@ -257,7 +290,7 @@ fn setup(mut commands: Commands, mut materials: ResMut<Assets<ColorMaterial>>) {
     commands
         .spawn(UiCameraComponents::default())
         .insert_resource(MainCamera::default())
-        .spawn((make_level_plan(bounds.top - bounds.bottom),))
+        .spawn((make_level_plan(bounds, &app_state),))
         .insert_resource(LevelBounds(bounds));
 }
 
@@ -556,11 +589,7 @@ fn player_health_ui(
 ) {
     if let Some((_player, health)) = player_query.iter().iter().next() {
         for (ui, mut draw) in &mut ui_query.iter() {
-            if ui.0 > health.0 {
-                draw.is_visible = false;
-            } else {
-                draw.is_visible = true;
-            }
+            draw.is_visible = ui.0 <= health.0;
         }
     }
 }
@@ -636,10 +665,10 @@ fn flash(
 ) {
     if flash.0 > 0 {
         flash.0 -= 1;
-        if let Some(mut material) = materials.get_mut(material) {
+        if let Some(material) = materials.get_mut(material) {
             material.color.a = 0.1;
         }
-    } else if let Some(mut material) = materials.get_mut(material) {
+    } else if let Some(material) = materials.get_mut(material) {
         material.color.a = 1.0;
     }
 }
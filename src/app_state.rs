@@ -0,0 +1,200 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+
+use crate::{LevelPlanElement, StepInfo};
+
+struct StateInner<S> {
+    current: S,
+    previous: Option<S>,
+    next: Option<S>,
+}
+
+/// The state of the app, such as playing, cutscene or victory, shared
+/// between plans and systems. Insert it as a resource, run
+/// `app_state_system` to apply transitions and hand clones to the
+/// `EnterAppState` and `WhileInAppState` elements of plans.
+///
+/// Clones share the same state.
+pub struct AppState<S> {
+    inner: Arc<Mutex<StateInner<S>>>,
+}
+impl<S> Clone for AppState<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+impl<S: Clone + PartialEq> AppState<S> {
+    pub fn new(initial: S) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(StateInner {
+                current: initial,
+                previous: None,
+                next: None,
+            })),
+        }
+    }
+
+    pub fn current(&self) -> S {
+        self.inner.lock().unwrap().current.clone()
+    }
+
+    pub fn is(&self, state: &S) -> bool {
+        self.inner.lock().unwrap().current == *state
+    }
+
+    pub fn previous(&self) -> Option<S> {
+        self.inner.lock().unwrap().previous.clone()
+    }
+
+    /// Queues a transition, applied on the next run of `app_state_system`.
+    /// Of several transitions queued in between only the last is applied.
+    pub fn set(&self, state: S) {
+        self.inner.lock().unwrap().next = Some(state);
+    }
+
+    pub fn pending(&self) -> Option<S> {
+        self.inner.lock().unwrap().next.clone()
+    }
+
+    fn apply(&self) -> Option<AppStateChanged<S>> {
+        let mut inner = self.inner.lock().unwrap();
+        let next = inner.next.take()?;
+        if next == inner.current {
+            return None;
+        }
+        let from = std::mem::replace(&mut inner.current, next.clone());
+        inner.previous = Some(from.clone());
+        Some(AppStateChanged { from, to: next })
+    }
+}
+
+/// Sent by `app_state_system` for every applied transition when
+/// `Events<AppStateChanged<S>>` has been added to the app.
+#[derive(Clone, Debug)]
+pub struct AppStateChanged<S> {
+    pub from: S,
+    pub to: S,
+}
+
+pub fn app_state_system<S: Clone + PartialEq + Send + Sync + 'static>(
+    _world: &mut World,
+    resources: &mut Resources,
+) {
+    let changed = match resources.get::<AppState<S>>() {
+        Some(state) => state.apply(),
+        None => None,
+    };
+    if let (Some(changed), Some(mut events)) =
+        (changed, resources.get_mut::<Events<AppStateChanged<S>>>())
+    {
+        events.send(changed);
+    }
+}
+
+/// Moves the app into a state when activated and completes immediately.
+pub struct EnterAppState<S> {
+    app_state: AppState<S>,
+    state: S,
+}
+impl<S> EnterAppState<S> {
+    pub fn new(app_state: &AppState<S>, state: S) -> Self {
+        Self {
+            app_state: app_state.clone(),
+            state,
+        }
+    }
+}
+impl<T, S: Clone + PartialEq + Send + Sync + 'static> LevelPlanElement<T> for EnterAppState<S> {
+    fn step(
+        &mut self,
+        _level: Entity,
+        _commands: &mut Commands,
+        _context: &mut T,
+        _info: &StepInfo,
+    ) -> bool {
+        false
+    }
+
+    fn activate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {
+        self.app_state.set(self.state.clone());
+    }
+}
+
+/// Runs `element` while the app is in the given state, completing when the
+/// app leaves it or `element` completes.
+pub struct WhileInAppState<T, S> {
+    app_state: AppState<S>,
+    state: S,
+    element: Box<dyn LevelPlanElement<T>>,
+}
+impl<T, S> WhileInAppState<T, S> {
+    pub fn new(
+        app_state: &AppState<S>,
+        state: S,
+        element: impl LevelPlanElement<T> + 'static,
+    ) -> Self {
+        Self {
+            app_state: app_state.clone(),
+            state,
+            element: Box::new(element),
+        }
+    }
+}
+impl<T, S: Clone + PartialEq + Send + Sync + 'static> LevelPlanElement<T>
+    for WhileInAppState<T, S>
+{
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        if self.app_state.is(&self.state) {
+            self.element.step(level, commands, context, info)
+        } else {
+            false
+        }
+    }
+
+    fn activate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.element.activate(level, commands, context);
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.element.deactivate(level, commands, context);
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.element.suspend(level, commands, context);
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.element.resume(level, commands, context);
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        self.element.jump_to(label, level, commands, context)
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        vec![self.element.as_ref()]
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        vec![&mut self.element]
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        vec![0]
+    }
+}
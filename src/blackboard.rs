@@ -0,0 +1,167 @@
+use std::{any::Any, collections::HashMap, marker::PhantomData, ops::Add};
+
+use bevy::prelude::*;
+
+use crate::{LevelContext, LevelPlanElement, StepInfo};
+
+pub struct VarKey<V> {
+    name: &'static str,
+    _marker: PhantomData<fn() -> V>,
+}
+impl<V> VarKey<V> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+impl<V> Clone for VarKey<V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<V> Copy for VarKey<V> {}
+
+/// Typed variables shared by every element of the plan on the same entity.
+///
+/// Spawn it next to the `LevelPlan` and expose it through
+/// `LevelContext::blackboard`/`blackboard_mut`; `level_plan_system` swaps it
+/// into the context while that entity's plan is stepped. If the entity has
+/// none, the context's blackboard is inserted after the plan's first step.
+#[derive(Default)]
+pub struct Blackboard {
+    vars: HashMap<&'static str, Box<dyn Any + Send + Sync>>,
+}
+impl Blackboard {
+    pub fn with<V: Send + Sync + 'static>(mut self, key: VarKey<V>, value: V) -> Self {
+        self.set(key, value);
+        self
+    }
+
+    pub fn get<V: 'static>(&self, key: VarKey<V>) -> Option<&V> {
+        self.vars.get(key.name).and_then(|v| v.downcast_ref())
+    }
+
+    pub fn get_mut<V: 'static>(&mut self, key: VarKey<V>) -> Option<&mut V> {
+        self.vars.get_mut(key.name).and_then(|v| v.downcast_mut())
+    }
+
+    pub fn set<V: Send + Sync + 'static>(&mut self, key: VarKey<V>, value: V) {
+        self.vars.insert(key.name, Box::new(value));
+    }
+
+    pub fn remove<V: 'static>(&mut self, key: VarKey<V>) -> Option<V> {
+        if self.get(key).is_some() {
+            self.vars
+                .remove(key.name)
+                .and_then(|v| v.downcast().ok())
+                .map(|v| *v)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains<V: 'static>(&self, key: VarKey<V>) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.vars.clear();
+    }
+}
+
+pub fn var_condition<T: LevelContext, V: 'static>(
+    key: VarKey<V>,
+    predicate: impl Fn(&V) -> bool + Send + Sync + 'static,
+) -> impl Fn(&T) -> bool + Send + Sync + 'static {
+    move |context| {
+        context
+            .blackboard()
+            .and_then(|blackboard| blackboard.get(key))
+            .is_some_and(&predicate)
+    }
+}
+
+pub fn var_equals<T: LevelContext, V: PartialEq + Send + Sync + 'static>(
+    key: VarKey<V>,
+    value: V,
+) -> impl Fn(&T) -> bool + Send + Sync + 'static {
+    var_condition(key, move |v| *v == value)
+}
+
+pub fn var_is_set<T: LevelContext, V: 'static>(
+    key: VarKey<V>,
+) -> impl Fn(&T) -> bool + Send + Sync + 'static {
+    var_condition(key, |_| true)
+}
+
+pub struct SetVar<V> {
+    key: VarKey<V>,
+    value: V,
+}
+impl<V> SetVar<V> {
+    pub fn new(key: VarKey<V>, value: V) -> Self {
+        Self { key, value }
+    }
+}
+impl<T: LevelContext, V: Clone + Send + Sync + 'static> LevelPlanElement<T> for SetVar<V> {
+    fn step(
+        &mut self,
+        _level: Entity,
+        _commands: &mut Commands,
+        _context: &mut T,
+        _info: &StepInfo,
+    ) -> bool {
+        false
+    }
+
+    fn activate(&mut self, _level: Entity, _commands: &mut Commands, context: &mut T) {
+        if let Some(blackboard) = context.blackboard_mut() {
+            blackboard.set(self.key, self.value.clone());
+        }
+    }
+
+    fn label(&self) -> String {
+        format!("SetVar({})", self.key.name())
+    }
+}
+
+pub struct IncrementVar<V> {
+    key: VarKey<V>,
+    amount: V,
+}
+impl<V> IncrementVar<V> {
+    pub fn new(key: VarKey<V>, amount: V) -> Self {
+        Self { key, amount }
+    }
+}
+impl<T: LevelContext, V> LevelPlanElement<T> for IncrementVar<V>
+where
+    V: Add<Output = V> + Default + Clone + Send + Sync + 'static,
+{
+    fn step(
+        &mut self,
+        _level: Entity,
+        _commands: &mut Commands,
+        _context: &mut T,
+        _info: &StepInfo,
+    ) -> bool {
+        false
+    }
+
+    fn activate(&mut self, _level: Entity, _commands: &mut Commands, context: &mut T) {
+        if let Some(blackboard) = context.blackboard_mut() {
+            let current = blackboard.get(self.key).cloned().unwrap_or_default();
+            blackboard.set(self.key, current + self.amount.clone());
+        }
+    }
+
+    fn label(&self) -> String {
+        format!("IncrementVar({})", self.key.name())
+    }
+}
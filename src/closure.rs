@@ -0,0 +1,354 @@
+use bevy::prelude::*;
+
+use crate::{LevelPlanElement, StepInfo};
+
+type Action<T, S> = Box<dyn FnMut(&mut S, Entity, &mut Commands, &mut T) + Send + Sync + 'static>;
+type StepAction<T, S> =
+    Box<dyn FnMut(&mut S, Entity, &mut Commands, &mut T) -> bool + Send + Sync + 'static>;
+
+/// An element built from closures. Each closure gets the element's state,
+/// which is reset to a clone of the initial state on every activation.
+/// Suspending and resuming keeps the state and runs `on_suspend` and
+/// `on_resume` rather than `on_deactivate` and `on_activate`. An element
+/// deactivated while suspended still runs `on_deactivate`, and only once.
+/// Without an `on_step` closure the element keeps running until its parent
+/// deactivates it.
+pub struct FnElement<T, S = ()> {
+    name: Option<String>,
+    initial: S,
+    state: S,
+    on_activate: Option<Action<T, S>>,
+    on_step: Option<StepAction<T, S>>,
+    on_deactivate: Option<Action<T, S>>,
+    on_suspend: Option<Action<T, S>>,
+    on_resume: Option<Action<T, S>>,
+    active: bool,
+}
+impl<T> FnElement<T, ()> {
+    pub fn new() -> Self {
+        Self::with_state(())
+    }
+}
+impl<T> Default for FnElement<T, ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T, S: Clone> FnElement<T, S> {
+    pub fn with_state(state: S) -> Self {
+        Self {
+            name: None,
+            initial: state.clone(),
+            state,
+            on_activate: None,
+            on_step: None,
+            on_deactivate: None,
+            on_suspend: None,
+            on_resume: None,
+            active: false,
+        }
+    }
+
+    /// The label shown when tracing or exporting the plan.
+    pub fn named(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn on_activate(
+        mut self,
+        action: impl FnMut(&mut S, Entity, &mut Commands, &mut T) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_activate = Some(Box::new(action));
+        self
+    }
+
+    /// Called every step; returning `false` completes the element.
+    pub fn on_step(
+        mut self,
+        action: impl FnMut(&mut S, Entity, &mut Commands, &mut T) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.on_step = Some(Box::new(action));
+        self
+    }
+
+    pub fn on_deactivate(
+        mut self,
+        action: impl FnMut(&mut S, Entity, &mut Commands, &mut T) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_deactivate = Some(Box::new(action));
+        self
+    }
+
+    /// Called when an interrupt or a blocking layer pauses the element, for
+    /// example to freeze what `on_activate` spawned.
+    pub fn on_suspend(
+        mut self,
+        action: impl FnMut(&mut S, Entity, &mut Commands, &mut T) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_suspend = Some(Box::new(action));
+        self
+    }
+
+    pub fn on_resume(
+        mut self,
+        action: impl FnMut(&mut S, Entity, &mut Commands, &mut T) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_resume = Some(Box::new(action));
+        self
+    }
+}
+impl<T, S: Clone + Send + Sync> LevelPlanElement<T> for FnElement<T, S> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        _info: &StepInfo,
+    ) -> bool {
+        match self.on_step.as_mut() {
+            Some(action) => action(&mut self.state, level, commands, context),
+            None => true,
+        }
+    }
+
+    fn activate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.state = self.initial.clone();
+        self.active = true;
+        if let Some(action) = self.on_activate.as_mut() {
+            action(&mut self.state, level, commands, context);
+        }
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if !std::mem::replace(&mut self.active, false) {
+            return;
+        }
+        if let Some(action) = self.on_deactivate.as_mut() {
+            action(&mut self.state, level, commands, context);
+        }
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(action) = self.on_suspend.as_mut() {
+            action(&mut self.state, level, commands, context);
+        }
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(action) = self.on_resume.as_mut() {
+            action(&mut self.state, level, commands, context);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.state = self.initial.clone();
+        self.active = false;
+    }
+
+    fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| "FnElement".to_string())
+    }
+}
+
+/// Runs a closure once when activated and completes immediately.
+pub struct OnActivate<T>(FnElement<T>);
+impl<T> OnActivate<T> {
+    pub fn new(
+        mut action: impl FnMut(Entity, &mut Commands, &mut T) + Send + Sync + 'static,
+    ) -> Self {
+        Self(
+            FnElement::new()
+                .on_activate(move |_, level, commands, context| action(level, commands, context))
+                .on_step(|_, _, _, _| false),
+        )
+    }
+}
+impl<T> LevelPlanElement<T> for OnActivate<T> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        self.0.step(level, commands, context, info)
+    }
+
+    fn activate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.0.activate(level, commands, context);
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.0.deactivate(level, commands, context);
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.0.suspend(level, commands, context);
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.0.resume(level, commands, context);
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+/// Runs a closure every step until it returns `false`.
+pub struct OnStep<T, S = ()>(FnElement<T, S>);
+impl<T> OnStep<T> {
+    pub fn new(
+        mut action: impl FnMut(Entity, &mut Commands, &mut T) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self::with_state((), move |_, level, commands, context| {
+            action(level, commands, context)
+        })
+    }
+}
+impl<T, S: Clone> OnStep<T, S> {
+    /// Like `new` but the closure also gets state which starts as a clone of
+    /// `state` on every activation.
+    pub fn with_state(
+        state: S,
+        action: impl FnMut(&mut S, Entity, &mut Commands, &mut T) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self(FnElement::with_state(state).on_step(action))
+    }
+}
+impl<T, S: Clone + Send + Sync> LevelPlanElement<T> for OnStep<T, S> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        self.0.step(level, commands, context, info)
+    }
+
+    fn activate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.0.activate(level, commands, context);
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.0.deactivate(level, commands, context);
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.0.suspend(level, commands, context);
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.0.resume(level, commands, context);
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+/// Keeps running and runs a closure when its parent deactivates it.
+pub struct OnDeactivate<T>(FnElement<T>);
+impl<T> OnDeactivate<T> {
+    pub fn new(
+        mut action: impl FnMut(Entity, &mut Commands, &mut T) + Send + Sync + 'static,
+    ) -> Self {
+        Self(
+            FnElement::new()
+                .on_deactivate(move |_, level, commands, context| action(level, commands, context)),
+        )
+    }
+}
+impl<T> LevelPlanElement<T> for OnDeactivate<T> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        self.0.step(level, commands, context, info)
+    }
+
+    fn activate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.0.activate(level, commands, context);
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.0.deactivate(level, commands, context);
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.0.suspend(level, commands, context);
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.0.resume(level, commands, context);
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{activate, deactivate, step, Log},
+        Interruptible,
+    };
+
+    #[test]
+    fn aborting_a_suspended_element_deactivates_it_once() {
+        let log = Log::default();
+        let cleanup = log.clone();
+        let mut element =
+            Interruptible::new(OnDeactivate::new(move |_, _, _| cleanup.push("cleanup")))
+                .abort_on(|phase: &u32| *phase == 2, log.probe("abort", 1))
+                .on(|phase: &u32| *phase == 1, log.probe("pause", 100));
+        let mut phase = 0;
+        activate(&mut element, &mut phase);
+        phase = 1;
+        step(&mut element, &mut phase);
+        phase = 2;
+        step(&mut element, &mut phase);
+        assert!(!step(&mut element, &mut phase));
+        deactivate(&mut element, &mut phase);
+        assert_eq!(
+            log.take(),
+            [
+                "pause activate",
+                "pause deactivate",
+                "cleanup",
+                "abort activate",
+                "abort step",
+                "abort deactivate",
+            ]
+        );
+    }
+
+    #[test]
+    fn suspend_and_resume_keep_state() {
+        let log = Log::default();
+        let events = log.clone();
+        let mut element = FnElement::with_state(0)
+            .on_step(|count: &mut u32, _, _, _: &mut ()| {
+                *count += 1;
+                true
+            })
+            .on_suspend(move |count, _, _, _| events.push(format!("suspend {}", count)))
+            .on_resume({
+                let events = log.clone();
+                move |count, _, _, _| events.push(format!("resume {}", count))
+            });
+        activate(&mut element, &mut ());
+        step(&mut element, &mut ());
+        step(&mut element, &mut ());
+        element.suspend(Entity::new(0), &mut Commands::default(), &mut ());
+        element.resume(Entity::new(0), &mut Commands::default(), &mut ());
+        assert_eq!(log.take(), ["suspend 2", "resume 2"]);
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use bevy::{ecs::DynamicBundle, prelude::*};
+
+use crate::{LevelPlanElement, StepInfo};
+
+type Request<T> = Box<dyn FnMut(Entity, &mut Commands, &mut T, &StepInfo) -> bool + Send + 'static>;
+type BoxedFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+type Factory<T> = Box<dyn Fn(CoroutineContext<T>) -> BoxedFuture + Send + Sync + 'static>;
+
+/// The requests of every `Wait` the body is currently awaiting, in the
+/// order they were first polled, so `join!` and `select!` can wait on several
+/// at once.
+struct Shared<T> {
+    requests: Vec<(u64, Request<T>)>,
+    next_id: u64,
+}
+
+/// Handle passed to the body of a `Coroutine`. Its futures only make
+/// progress when awaited inside that body.
+pub struct CoroutineContext<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+impl<T> Clone for CoroutineContext<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+impl<T: 'static> CoroutineContext<T> {
+    /// Calls `poll` every step with the plan's entity, commands and context
+    /// until it returns a value, which the future then resolves to.
+    pub fn poll_with<R: Send + 'static>(
+        &self,
+        mut poll: impl FnMut(Entity, &mut Commands, &mut T) -> Option<R> + Send + 'static,
+    ) -> Wait<T, R> {
+        self.poll_timed(move |level, commands, context, _info| poll(level, commands, context))
+    }
+
+    fn poll_timed<R, F>(&self, mut poll: F) -> Wait<T, R>
+    where
+        R: Send + 'static,
+        F: FnMut(Entity, &mut Commands, &mut T, &StepInfo) -> Option<R> + Send + 'static,
+    {
+        let result = Arc::new(Mutex::new(None));
+        let slot = result.clone();
+        Wait {
+            shared: self.shared.clone(),
+            id: None,
+            request: Some(Box::new(move |level, commands, context, info| {
+                match poll(level, commands, context, info) {
+                    Some(value) => {
+                        *slot.lock().unwrap() = Some(value);
+                        true
+                    }
+                    None => false,
+                }
+            })),
+            result,
+        }
+    }
+
+    /// Runs `action` once with the plan's entity, commands and context.
+    pub fn run<R: Send + 'static>(
+        &self,
+        action: impl FnOnce(Entity, &mut Commands, &mut T) -> R + Send + 'static,
+    ) -> Wait<T, R> {
+        let mut action = Some(action);
+        self.poll_with(move |level, commands, context| {
+            action.take().map(|action| action(level, commands, context))
+        })
+    }
+
+    /// Spawns `bundle` and resolves to the new entity.
+    pub fn spawn(
+        &self,
+        bundle: impl DynamicBundle + Send + Sync + 'static,
+    ) -> Wait<T, Option<Entity>> {
+        self.run(move |_level, commands, _context| commands.spawn(bundle).current_entity())
+    }
+
+    pub fn until(&self, condition: impl Fn(&T) -> bool + Send + 'static) -> Wait<T, ()> {
+        self.poll_with(
+            move |_level, _commands, context| {
+                if condition(context) {
+                    Some(())
+                } else {
+                    None
+                }
+            },
+        )
+    }
+
+    /// Resolves once `duration` of plan time has passed.
+    pub fn wait(&self, duration: Duration) -> Wait<T, ()> {
+        let mut start = None;
+        self.poll_timed(move |_level, _commands, _context, info| {
+            if info.elapsed - *start.get_or_insert(info.elapsed) >= duration {
+                Some(())
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn wait_secs(&self, seconds: f32) -> Wait<T, ()> {
+        self.wait(Duration::from_secs_f32(seconds))
+    }
+
+    /// Resolves on the next step of the plan.
+    pub fn next_step(&self) -> Wait<T, ()> {
+        let mut first = true;
+        self.poll_with(move |_level, _commands, _context| {
+            if std::mem::take(&mut first) {
+                None
+            } else {
+                Some(())
+            }
+        })
+    }
+}
+
+/// A future returned by `CoroutineContext`. Dropping it before it resolves,
+/// like `select!` does with the branches that lost, withdraws its request.
+pub struct Wait<T, R> {
+    shared: Arc<Mutex<Shared<T>>>,
+    id: Option<u64>,
+    request: Option<Request<T>>,
+    result: Arc<Mutex<Option<R>>>,
+}
+impl<T, R> Future for Wait<T, R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<R> {
+        let this = self.get_mut();
+        if let Some(result) = this.result.lock().unwrap().take() {
+            return Poll::Ready(result);
+        }
+        if let Some(request) = this.request.take() {
+            let mut shared = this.shared.lock().unwrap();
+            let id = shared.next_id;
+            shared.next_id += 1;
+            shared.requests.push((id, request));
+            this.id = Some(id);
+        }
+        Poll::Pending
+    }
+}
+impl<T, R> Drop for Wait<T, R> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            if let Ok(mut shared) = self.shared.lock() {
+                shared.requests.retain(|(request, _)| *request != id);
+            }
+        }
+    }
+}
+
+/// Runs the future returned by `body` as an element, polling it every step
+/// and completing when it finishes, so a script like
+/// `|ctx| async move { ctx.wait_secs(2.0).await; ctx.spawn((Boss,)).await; }`
+/// can be used anywhere in a plan. Waits which resolve immediately let the
+/// body continue within the same step, and several waits may be pending at
+/// once, as with `futures::join!` or `futures::select!`.
+///
+/// A fresh future is created on every activation and deactivating the
+/// element drops it, cancelling whatever it was waiting on, also when it is
+/// deactivated while suspended. Suspending keeps the future so it continues
+/// where it left off on resume.
+pub struct Coroutine<T> {
+    body: Factory<T>,
+    shared: Arc<Mutex<Shared<T>>>,
+    future: Option<Mutex<BoxedFuture>>,
+}
+impl<T: 'static> Coroutine<T> {
+    pub fn new<F: Future<Output = ()> + Send + 'static>(
+        body: impl Fn(CoroutineContext<T>) -> F + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            body: Box::new(move |context| Box::pin(body(context))),
+            shared: Arc::new(Mutex::new(Shared {
+                requests: Vec::new(),
+                next_id: 0,
+            })),
+            future: None,
+        }
+    }
+}
+impl<T: 'static> LevelPlanElement<T> for Coroutine<T> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        let future = match self.future.as_mut() {
+            Some(future) => future.get_mut().unwrap(),
+            None => return false,
+        };
+        let mut cx = Context::from_waker(Waker::noop());
+        // Every pending request runs once per step; after the body was polled
+        // only the requests it made since are run again.
+        let mut first_new = 0;
+        let mut resolved = self.shared.lock().unwrap().requests.is_empty();
+        loop {
+            let mut requests = std::mem::take(&mut self.shared.lock().unwrap().requests);
+            let pending = requests.len();
+            requests.retain_mut(|(id, request)| {
+                *id < first_new || !request(level, commands, context, info)
+            });
+            resolved |= requests.len() < pending;
+            self.shared.lock().unwrap().requests = requests;
+            if !resolved {
+                return true;
+            }
+            first_new = self.shared.lock().unwrap().next_id;
+            if future.as_mut().poll(&mut cx).is_ready() {
+                self.future = None;
+                return false;
+            }
+            resolved = false;
+        }
+    }
+
+    fn activate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {
+        self.shared.lock().unwrap().requests.clear();
+        let context = CoroutineContext {
+            shared: self.shared.clone(),
+        };
+        self.future = Some(Mutex::new((self.body)(context)));
+    }
+
+    fn deactivate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {
+        self.future = None;
+        self.shared.lock().unwrap().requests.clear();
+    }
+
+    fn suspend(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {}
+
+    fn resume(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {}
+
+    fn reset(&mut self) {
+        self.future = None;
+        self.shared.lock().unwrap().requests.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{activate, step, Log},
+        PlanSignals,
+    };
+
+    #[test]
+    fn awaits_two_signals_at_once() {
+        let signals = PlanSignals::default();
+        let log = Log::default();
+        let mut coroutine = Coroutine::new({
+            let signals = signals.clone();
+            let log = log.clone();
+            move |ctx: CoroutineContext<()>| {
+                let (doors, alarm) = (signals.clone(), signals.clone());
+                let log = log.clone();
+                async move {
+                    futures::join!(
+                        ctx.until(move |_| doors.is_raised("doors")),
+                        ctx.until(move |_| alarm.is_raised("alarm")),
+                    );
+                    log.push("both");
+                }
+            }
+        });
+        activate(&mut coroutine, &mut ());
+        assert!(step(&mut coroutine, &mut ()));
+        signals.raise("alarm");
+        assert!(step(&mut coroutine, &mut ()));
+        signals.lower("alarm");
+        assert!(step(&mut coroutine, &mut ()));
+        assert!(log.take().is_empty());
+        signals.raise("doors");
+        assert!(!step(&mut coroutine, &mut ()));
+        assert_eq!(log.take(), ["both"]);
+    }
+}
@@ -0,0 +1,313 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{LevelPlanElement, StepInfo};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DirectorPhase {
+    /// Running encounters until intensity reaches the top of the band.
+    BuildUp,
+    /// Holding at the top of the band for the peak duration.
+    Peak,
+    /// Running only the relax element until intensity falls to the bottom of
+    /// the band.
+    Relax,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Running {
+    Encounter(usize),
+    Relax,
+}
+
+struct Encounter<T> {
+    name: String,
+    rating: f32,
+    element: Box<dyn LevelPlanElement<T>>,
+}
+
+/// Picks encounters to keep a smoothed intensity score, computed from the
+/// context, cycling through build-up, peak and relax phases inside the band
+/// `low..high`. Never completes on its own.
+///
+/// Each encounter has a rating for how much intensity it is expected to add.
+/// During build-up the director starts the highest rated encounter which
+/// fits the remaining headroom, or the lowest rated one if none fits, and
+/// picks again whenever the running encounter completes.
+pub struct Director<T> {
+    measure: Box<dyn Fn(&T) -> f32 + Send + Sync + 'static>,
+    low: f32,
+    high: f32,
+    smoothing: f32,
+    peak_duration: Duration,
+    encounters: Vec<Encounter<T>>,
+    relax: Option<Box<dyn LevelPlanElement<T>>>,
+    intensity: f32,
+    phase: DirectorPhase,
+    peak_start: Option<Duration>,
+    running: Option<Running>,
+}
+impl<T> Director<T> {
+    pub fn new(low: f32, high: f32, measure: impl Fn(&T) -> f32 + Send + Sync + 'static) -> Self {
+        Self {
+            measure: Box::new(measure),
+            low,
+            high,
+            smoothing: 6.0,
+            peak_duration: Duration::from_secs(5),
+            encounters: Vec::new(),
+            relax: None,
+            intensity: 0.0,
+            phase: DirectorPhase::BuildUp,
+            peak_start: None,
+            running: None,
+        }
+    }
+
+    pub fn encounter(
+        mut self,
+        name: impl Into<String>,
+        rating: f32,
+        element: impl LevelPlanElement<T> + 'static,
+    ) -> Self {
+        self.encounters.push(Encounter {
+            name: name.into(),
+            rating,
+            element: Box::new(element),
+        });
+        self
+    }
+
+    pub fn relax_with(mut self, element: impl LevelPlanElement<T> + 'static) -> Self {
+        self.relax = Some(Box::new(element));
+        self
+    }
+
+    pub fn peak_for(mut self, duration: Duration) -> Self {
+        self.peak_duration = duration;
+        self
+    }
+
+    /// How quickly the tracked intensity follows the measured value, per
+    /// second: the gap between them shrinks by a factor of e every
+    /// `1 / smoothing` seconds, whatever the frame rate. 0 never follows.
+    pub fn smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing.max(0.0);
+        self
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    pub fn phase(&self) -> DirectorPhase {
+        self.phase
+    }
+
+    pub fn current_encounter(&self) -> Option<&str> {
+        match self.running {
+            Some(Running::Encounter(index)) => Some(self.encounters[index].name.as_str()),
+            _ => None,
+        }
+    }
+
+    fn choose(&self) -> Option<usize> {
+        let headroom = self.high - self.intensity;
+        let by_rating = |a: &(usize, &Encounter<T>), b: &(usize, &Encounter<T>)| {
+            a.1.rating
+                .partial_cmp(&b.1.rating)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        };
+        self.encounters
+            .iter()
+            .enumerate()
+            .filter(|(_, encounter)| encounter.rating <= headroom)
+            .max_by(by_rating)
+            .or_else(|| self.encounters.iter().enumerate().min_by(by_rating))
+            .map(|(index, _)| index)
+    }
+
+    fn running_element(&mut self) -> Option<&mut Box<dyn LevelPlanElement<T>>> {
+        match self.running {
+            Some(Running::Encounter(index)) => Some(&mut self.encounters[index].element),
+            Some(Running::Relax) => self.relax.as_mut(),
+            None => None,
+        }
+    }
+
+    fn start(&mut self, running: Running, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.stop(level, commands, context);
+        self.running = Some(running);
+        if let Some(element) = self.running_element() {
+            element.activate(level, commands, context);
+        } else {
+            self.running = None;
+        }
+    }
+
+    fn stop(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(element) = self.running_element() {
+            element.deactivate(level, commands, context);
+        }
+        self.running = None;
+    }
+}
+impl<T> LevelPlanElement<T> for Director<T> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        let measured = (self.measure)(context);
+        let factor = 1.0 - (-self.smoothing * info.delta_seconds()).exp();
+        self.intensity += (measured - self.intensity) * factor;
+
+        match self.phase {
+            DirectorPhase::BuildUp => {
+                if self.intensity >= self.high {
+                    self.phase = DirectorPhase::Peak;
+                    self.peak_start = Some(info.elapsed);
+                } else if self.running.is_none() {
+                    if let Some(index) = self.choose() {
+                        self.start(Running::Encounter(index), level, commands, context);
+                    }
+                }
+            }
+            DirectorPhase::Peak => {
+                if self
+                    .peak_start
+                    .is_none_or(|start| info.elapsed - start >= self.peak_duration)
+                {
+                    self.phase = DirectorPhase::Relax;
+                    self.start(Running::Relax, level, commands, context);
+                }
+            }
+            DirectorPhase::Relax => {
+                if self.intensity <= self.low {
+                    self.phase = DirectorPhase::BuildUp;
+                    self.stop(level, commands, context);
+                    if let Some(index) = self.choose() {
+                        self.start(Running::Encounter(index), level, commands, context);
+                    }
+                }
+            }
+        }
+
+        if let Some(element) = self.running_element() {
+            if !element.step(level, commands, context, info) {
+                self.stop(level, commands, context);
+            }
+        }
+        true
+    }
+
+    fn activate(&mut self, _level: Entity, _commands: &mut Commands, context: &mut T) {
+        self.intensity = (self.measure)(context);
+        self.phase = DirectorPhase::BuildUp;
+        self.peak_start = None;
+        self.running = None;
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.stop(level, commands, context);
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(element) = self.running_element() {
+            element.suspend(level, commands, context);
+        }
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(element) = self.running_element() {
+            element.resume(level, commands, context);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.intensity = 0.0;
+        self.phase = DirectorPhase::BuildUp;
+        self.peak_start = None;
+        self.running = None;
+        for child in self.children_mut() {
+            child.reset();
+        }
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        if let Some(element) = self.running_element() {
+            element.jump_to(label, level, commands, context)
+        } else {
+            false
+        }
+    }
+
+    fn label(&self) -> String {
+        format!("Director({}..{})", self.low, self.high)
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        self.encounters
+            .iter()
+            .map(|encounter| encounter.element.as_ref())
+            .chain(self.relax.as_deref())
+            .collect()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        self.encounters
+            .iter_mut()
+            .map(|encounter| &mut encounter.element)
+            .chain(self.relax.as_mut())
+            .collect()
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        match self.running {
+            Some(Running::Encounter(index)) => vec![index],
+            Some(Running::Relax) => vec![self.encounters.len()],
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn intensity_after(steps: u32, delta: Duration) -> f32 {
+        let mut director = Director::new(0.2, 0.9, |measured: &f32| *measured);
+        let mut measured = 1.0;
+        let info = StepInfo {
+            delta,
+            ..Default::default()
+        };
+        for _ in 0..steps {
+            director.step(
+                Entity::new(0),
+                &mut Commands::default(),
+                &mut measured,
+                &info,
+            );
+        }
+        director.intensity()
+    }
+
+    #[test]
+    fn smoothing_is_independent_of_frame_rate() {
+        let fast = intensity_after(100, Duration::from_millis(5));
+        let slow = intensity_after(10, Duration::from_millis(50));
+        assert!((fast - slow).abs() < 1e-4, "{} != {}", fast, slow);
+        let expected = 1.0 - (-6.0f32 * 0.5).exp();
+        assert!((fast - expected).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,241 @@
+use std::marker::PhantomData;
+
+use bevy::prelude::*;
+
+use crate::{LevelPlanElement, StepInfo};
+
+pub struct WaitUntil<T> {
+    condition: Box<dyn Fn(&T) -> bool + Send + Sync + 'static>,
+}
+impl<T> WaitUntil<T> {
+    pub fn new(condition: impl Fn(&T) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            condition: Box::new(condition),
+        }
+    }
+}
+impl<T> LevelPlanElement<T> for WaitUntil<T> {
+    fn step(
+        &mut self,
+        _level: Entity,
+        _commands: &mut Commands,
+        context: &mut T,
+        _info: &StepInfo,
+    ) -> bool {
+        !(self.condition)(context)
+    }
+}
+
+/// Waits for the condition to hold, then runs `element` to completion once.
+pub struct OnceWhen<T> {
+    condition: Box<dyn Fn(&T) -> bool + Send + Sync + 'static>,
+    element: Box<dyn LevelPlanElement<T>>,
+    fired: bool,
+}
+impl<T> OnceWhen<T> {
+    pub fn new(
+        condition: impl Fn(&T) -> bool + Send + Sync + 'static,
+        element: impl LevelPlanElement<T> + 'static,
+    ) -> Self {
+        Self {
+            condition: Box::new(condition),
+            element: Box::new(element),
+            fired: false,
+        }
+    }
+}
+impl<T> LevelPlanElement<T> for OnceWhen<T> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        if !self.fired {
+            if (self.condition)(context) {
+                self.element.activate(level, commands, context);
+                self.fired = true;
+            } else {
+                return true;
+            }
+        }
+        self.element.step(level, commands, context, info)
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if self.fired {
+            self.element.deactivate(level, commands, context);
+            self.fired = false;
+        }
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if self.fired {
+            self.element.suspend(level, commands, context);
+        }
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if self.fired {
+            self.element.resume(level, commands, context);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.fired = false;
+        self.element.reset();
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        self.fired && self.element.jump_to(label, level, commands, context)
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        vec![self.element.as_ref()]
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        vec![&mut self.element]
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        if self.fired {
+            vec![0]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+pub trait EdgeKind: Send + Sync + 'static {
+    fn fired(previous: bool, current: bool) -> bool;
+}
+pub struct Rising;
+impl EdgeKind for Rising {
+    fn fired(previous: bool, current: bool) -> bool {
+        !previous && current
+    }
+}
+pub struct Falling;
+impl EdgeKind for Falling {
+    fn fired(previous: bool, current: bool) -> bool {
+        previous && !current
+    }
+}
+
+pub type OnRisingEdge<T> = OnEdge<T, Rising>;
+pub type OnFallingEdge<T> = OnEdge<T, Falling>;
+
+/// Runs `element` each time the condition changes in the direction given by
+/// `E`. The state of the condition at activation does not count as an edge,
+/// and an edge while `element` is still running restarts it. Never completes
+/// on its own.
+pub struct OnEdge<T, E> {
+    condition: Box<dyn Fn(&T) -> bool + Send + Sync + 'static>,
+    element: Box<dyn LevelPlanElement<T>>,
+    element_active: bool,
+    previous: bool,
+    _edge: PhantomData<E>,
+}
+impl<T, E: EdgeKind> OnEdge<T, E> {
+    pub fn new(
+        condition: impl Fn(&T) -> bool + Send + Sync + 'static,
+        element: impl LevelPlanElement<T> + 'static,
+    ) -> Self {
+        Self {
+            condition: Box::new(condition),
+            element: Box::new(element),
+            element_active: false,
+            previous: false,
+            _edge: PhantomData,
+        }
+    }
+}
+impl<T, E: EdgeKind> LevelPlanElement<T> for OnEdge<T, E> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        let current = (self.condition)(context);
+        if E::fired(self.previous, current) {
+            if self.element_active {
+                self.element.deactivate(level, commands, context);
+            }
+            self.element.activate(level, commands, context);
+            self.element_active = true;
+        }
+        self.previous = current;
+        if self.element_active && !self.element.step(level, commands, context, info) {
+            self.element.deactivate(level, commands, context);
+            self.element_active = false;
+        }
+        true
+    }
+
+    fn activate(&mut self, _level: Entity, _commands: &mut Commands, context: &mut T) {
+        self.previous = (self.condition)(context);
+        self.element_active = false;
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if self.element_active {
+            self.element.deactivate(level, commands, context);
+            self.element_active = false;
+        }
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if self.element_active {
+            self.element.suspend(level, commands, context);
+        }
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.previous = (self.condition)(context);
+        if self.element_active {
+            self.element.resume(level, commands, context);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.element_active = false;
+        self.previous = false;
+        self.element.reset();
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        self.element_active && self.element.jump_to(label, level, commands, context)
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        vec![self.element.as_ref()]
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        vec![&mut self.element]
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        if self.element_active {
+            vec![0]
+        } else {
+            Vec::new()
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use crate::{instrument::child_path, LevelPlan, LevelPlanElement};
+
+/// What to include besides the structure when exporting a plan with
+/// `LevelPlan::to_text_with` or `LevelPlan::to_dot_with`.
+#[derive(Clone, Default)]
+pub struct ExportOptions {
+    highlight_active: bool,
+    visits: HashMap<String, usize>,
+}
+impl ExportOptions {
+    /// Marks the elements that are currently running.
+    pub fn highlight_active(mut self) -> Self {
+        self.highlight_active = true;
+        self
+    }
+
+    /// Annotates elements with visit counts keyed by path, such as those
+    /// returned by `PlanTracer::visit_counts`.
+    pub fn visits(mut self, visits: HashMap<String, usize>) -> Self {
+        self.visits = visits;
+        self
+    }
+}
+
+struct Node {
+    path: String,
+    parent: Option<String>,
+    depth: usize,
+    label: String,
+    active: bool,
+}
+
+fn collect<T>(
+    element: &dyn LevelPlanElement<T>,
+    path: String,
+    parent: Option<String>,
+    depth: usize,
+    active: bool,
+    nodes: &mut Vec<Node>,
+) {
+    let active_children = element.active_children();
+    let children = element.children();
+    nodes.push(Node {
+        path: path.clone(),
+        parent,
+        depth,
+        label: element.label(),
+        active,
+    });
+    for (index, child) in children.into_iter().enumerate() {
+        collect(
+            child,
+            child_path(&path, index),
+            Some(path.clone()),
+            depth + 1,
+            active && active_children.contains(&index),
+            nodes,
+        );
+    }
+}
+
+/// Escapes `text` for a quoted DOT label, keeping line breaks and replacing
+/// other control characters, which DOT cannot show, with spaces.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            character if character.is_control() => escaped.push(' '),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+impl<T> LevelPlan<T> {
+    fn nodes(&self) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        collect(
+            self.plan.as_ref(),
+            "/".to_string(),
+            None,
+            0,
+            self.activated,
+            &mut nodes,
+        );
+        nodes
+    }
+
+    /// Renders the plan as an indented tree, one element per line.
+    pub fn to_text(&self) -> String {
+        self.to_text_with(&ExportOptions::default())
+    }
+
+    pub fn to_text_with(&self, options: &ExportOptions) -> String {
+        let mut text = String::new();
+        for node in self.nodes() {
+            text.push_str(&format!(
+                "{:indent$}{}",
+                "",
+                node.label,
+                indent = node.depth * 2
+            ));
+            if let Some(visits) = options.visits.get(&node.path) {
+                text.push_str(&format!(" x{}", visits));
+            }
+            if options.highlight_active && node.active {
+                text.push_str(" [active]");
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Renders the plan as a Graphviz digraph with one node per element,
+    /// named by its path.
+    pub fn to_dot(&self) -> String {
+        self.to_dot_with(&ExportOptions::default())
+    }
+
+    pub fn to_dot_with(&self, options: &ExportOptions) -> String {
+        let mut dot = String::from("digraph plan {\n    node [shape=box];\n");
+        for node in self.nodes() {
+            let mut label = escape(&node.label);
+            if let Some(visits) = options.visits.get(&node.path) {
+                label.push_str(&format!("\\nvisited {}", visits));
+            }
+            let style = if options.highlight_active && node.active {
+                ", style=filled, fillcolor=lightblue"
+            } else {
+                ""
+            };
+            dot.push_str(&format!(
+                "    \"{}\" [label=\"{}\"{}];\n",
+                node.path, label, style
+            ));
+            if let Some(parent) = node.parent {
+                dot.push_str(&format!("    \"{}\" -> \"{}\";\n", parent, node.path));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_are_escaped() {
+        assert_eq!(escape("a \"b\" \\ c"), "a \\\"b\\\" \\\\ c");
+        assert_eq!(escape("one\r\ntwo\tthree"), "one\\ntwo three");
+    }
+}
@@ -0,0 +1,419 @@
+use std::{collections::HashMap, fmt};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{LevelPlan, LevelPlanElement, Sequence};
+
+const MAX_DEPTH: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Repeat {
+    One,
+    Optional,
+    ZeroOrMore,
+    OneOrMore,
+}
+
+#[derive(Clone, Debug)]
+struct Item {
+    symbol: String,
+    repeat: Repeat,
+}
+
+fn parse_items(production: &str) -> Vec<Item> {
+    production
+        .split_whitespace()
+        .map(|token| {
+            let (symbol, repeat) = match token.chars().last() {
+                Some('?') => (&token[..token.len() - 1], Repeat::Optional),
+                Some('*') => (&token[..token.len() - 1], Repeat::ZeroOrMore),
+                Some('+') => (&token[..token.len() - 1], Repeat::OneOrMore),
+                _ => (token, Repeat::One),
+            };
+            Item {
+                symbol: symbol.to_string(),
+                repeat,
+            }
+        })
+        .collect()
+}
+
+/// The bookkeeping of a single generation attempt.
+#[derive(Default)]
+struct Attempt<'a> {
+    counts: HashMap<&'a str, usize>,
+    /// The symbol whose expansion exceeded `MAX_DEPTH`, if any.
+    too_deep: Option<&'a str>,
+}
+
+struct Terminal<T> {
+    length: f32,
+    factory: Box<dyn Fn() -> Box<dyn LevelPlanElement<T>> + Send + Sync + 'static>,
+}
+
+#[derive(Debug)]
+pub enum GrammarError {
+    UnknownSymbol(String),
+    RecursionLimit(String),
+    Unsatisfiable { attempts: usize },
+}
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GrammarError::UnknownSymbol(symbol) => write!(f, "unknown symbol '{}'", symbol),
+            GrammarError::RecursionLimit(symbol) => {
+                write!(f, "recursion limit reached expanding '{}'", symbol)
+            }
+            GrammarError::Unsatisfiable { attempts } => write!(
+                f,
+                "no plan satisfied the constraints after {} attempts",
+                attempts
+            ),
+        }
+    }
+}
+impl std::error::Error for GrammarError {}
+
+/// A weighted grammar from which concrete plans are generated.
+///
+/// Productions are written as whitespace separated symbols, each optionally
+/// suffixed with `?` (zero or one), `*` (zero or more) or `+` (one or more),
+/// for example `"Intro Wave+ Breather Boss"`. Terminals produce elements and
+/// carry a length used by the total length constraint. Each generated
+/// non-terminal becomes a `Sequence` of its expansion.
+pub struct PlanGrammar<T> {
+    rules: HashMap<String, Vec<(f32, Vec<Item>)>>,
+    terminals: HashMap<String, Terminal<T>>,
+    limits: HashMap<String, (usize, usize)>,
+    length: (f32, f32),
+    max_repeat: usize,
+    attempts: usize,
+}
+impl<T> Default for PlanGrammar<T> {
+    fn default() -> Self {
+        Self {
+            rules: HashMap::new(),
+            terminals: HashMap::new(),
+            limits: HashMap::new(),
+            length: (0.0, f32::MAX),
+            max_repeat: 4,
+            attempts: 100,
+        }
+    }
+}
+impl<T: 'static> PlanGrammar<T> {
+    pub fn rule(mut self, symbol: impl Into<String>, weight: f32, production: &str) -> Self {
+        self.rules
+            .entry(symbol.into())
+            .or_default()
+            .push((weight, parse_items(production)));
+        self
+    }
+
+    pub fn terminal<E: LevelPlanElement<T> + 'static>(
+        mut self,
+        symbol: impl Into<String>,
+        length: f32,
+        factory: impl Fn() -> E + Send + Sync + 'static,
+    ) -> Self {
+        self.terminals.insert(
+            symbol.into(),
+            Terminal {
+                length,
+                factory: Box::new(move || Box::new(factory())),
+            },
+        );
+        self
+    }
+
+    /// Bounds how many times `symbol` may appear in a generated plan.
+    pub fn limit(mut self, symbol: impl Into<String>, min: usize, max: usize) -> Self {
+        self.limits.insert(symbol.into(), (min, max));
+        self
+    }
+
+    /// Bounds the summed length of all terminals in a generated plan.
+    pub fn total_length(mut self, min: f32, max: f32) -> Self {
+        self.length = (min, max);
+        self
+    }
+
+    /// The most times a `*` or `+` item is repeated.
+    pub fn max_repeat(mut self, max_repeat: usize) -> Self {
+        self.max_repeat = max_repeat;
+        self
+    }
+
+    pub fn attempts(mut self, attempts: usize) -> Self {
+        self.attempts = attempts;
+        self
+    }
+
+    /// Generates plans from `seed` until one satisfies the limits and total
+    /// length. Attempts recursing deeper than a fixed limit are abandoned
+    /// like ones breaking a constraint; `RecursionLimit` is only returned
+    /// when every attempt was.
+    pub fn generate(&self, start: &str, seed: u64) -> Result<GeneratedPlan<T>, GrammarError> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut too_deep = None;
+        let mut only_too_deep = true;
+        for _ in 0..self.attempts {
+            let mut state = Attempt::default();
+            let tree = match self.expand(start, &mut rng, &mut state, 0)? {
+                Some(tree) => tree,
+                None => {
+                    match state.too_deep {
+                        Some(symbol) => too_deep = Some(symbol),
+                        None => only_too_deep = false,
+                    }
+                    continue;
+                }
+            };
+            only_too_deep = false;
+            let within_limits = self.limits.iter().all(|(symbol, (min, _))| {
+                state.counts.get(symbol.as_str()).copied().unwrap_or(0) >= *min
+            });
+            if within_limits && tree.length >= self.length.0 && tree.length <= self.length.1 {
+                let element = self.instantiate(&tree);
+                return Ok(GeneratedPlan { tree, element });
+            }
+        }
+        match too_deep.filter(|_| only_too_deep) {
+            Some(symbol) => Err(GrammarError::RecursionLimit(symbol.to_string())),
+            None => Err(GrammarError::Unsatisfiable {
+                attempts: self.attempts,
+            }),
+        }
+    }
+
+    /// Returns `Ok(None)` when this attempt broke a constraint and should be
+    /// abandoned.
+    fn expand<'a>(
+        &'a self,
+        symbol: &'a str,
+        rng: &mut StdRng,
+        state: &mut Attempt<'a>,
+        depth: usize,
+    ) -> Result<Option<PlanNode>, GrammarError> {
+        if depth > MAX_DEPTH {
+            state.too_deep = Some(symbol);
+            return Ok(None);
+        }
+        let count = state.counts.entry(symbol).or_insert(0);
+        *count += 1;
+        if let Some((_, max)) = self.limits.get(symbol) {
+            if *count > *max {
+                return Ok(None);
+            }
+        }
+
+        if let Some(terminal) = self.terminals.get(symbol) {
+            return Ok(Some(PlanNode {
+                symbol: symbol.to_string(),
+                length: terminal.length,
+                children: Vec::new(),
+            }));
+        }
+        let alternatives = self
+            .rules
+            .get(symbol)
+            .ok_or_else(|| GrammarError::UnknownSymbol(symbol.to_string()))?;
+        let total: f32 = alternatives.iter().map(|(weight, _)| weight).sum();
+        let mut choice = rng.gen_range(0.0, total.max(f32::EPSILON));
+        let items = alternatives
+            .iter()
+            .find(|(weight, _)| {
+                choice -= weight;
+                choice < 0.0
+            })
+            .or_else(|| alternatives.last())
+            .map(|(_, items)| items)
+            .ok_or_else(|| GrammarError::UnknownSymbol(symbol.to_string()))?;
+
+        let mut children = Vec::new();
+        for item in items {
+            let repeats = match item.repeat {
+                Repeat::One => 1,
+                Repeat::Optional => rng.gen_range(0, 2),
+                Repeat::ZeroOrMore => rng.gen_range(0, self.max_repeat + 1),
+                Repeat::OneOrMore => rng.gen_range(1, self.max_repeat.max(1) + 1),
+            };
+            for _ in 0..repeats {
+                match self.expand(&item.symbol, rng, state, depth + 1)? {
+                    Some(child) => children.push(child),
+                    None => return Ok(None),
+                }
+            }
+        }
+        Ok(Some(PlanNode {
+            symbol: symbol.to_string(),
+            length: children.iter().map(|child| child.length).sum(),
+            children,
+        }))
+    }
+
+    fn instantiate(&self, node: &PlanNode) -> Box<dyn LevelPlanElement<T>> {
+        if let Some(terminal) = self.terminals.get(&node.symbol) {
+            (terminal.factory)()
+        } else {
+            Box::new(
+                node.children
+                    .iter()
+                    .fold(Sequence::default(), |sequence, child| {
+                        sequence.push_boxed(self.instantiate(child))
+                    }),
+            )
+        }
+    }
+}
+
+/// The derivation a plan was generated from. `Display` renders it as an
+/// indented tree with the length of every node.
+#[derive(Clone, Debug)]
+pub struct PlanNode {
+    pub symbol: String,
+    pub length: f32,
+    pub children: Vec<PlanNode>,
+}
+impl PlanNode {
+    fn write(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{:indent$}{} ({})",
+            "",
+            self.symbol,
+            self.length,
+            indent = depth * 2
+        )?;
+        for child in &self.children {
+            child.write(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+impl fmt::Display for PlanNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+pub struct GeneratedPlan<T> {
+    pub tree: PlanNode,
+    element: Box<dyn LevelPlanElement<T>>,
+}
+impl<T: 'static> GeneratedPlan<T> {
+    pub fn into_element(self) -> Box<dyn LevelPlanElement<T>> {
+        self.element
+    }
+
+    pub fn into_plan(self) -> LevelPlan<T> {
+        LevelPlan::new(Sequence::default().push_boxed(self.element))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Nop;
+
+    fn symbols(node: &PlanNode) -> Vec<&str> {
+        node.children
+            .iter()
+            .map(|child| child.symbol.as_str())
+            .collect()
+    }
+
+    fn terminals() -> PlanGrammar<()> {
+        PlanGrammar::default()
+            .terminal("Wave", 100.0, || Nop)
+            .terminal("Breather", 50.0, || Nop)
+            .terminal("Boss", 200.0, || Nop)
+    }
+
+    #[test]
+    fn same_seed_generates_same_plan() {
+        let grammar =
+            terminals()
+                .rule("Level", 1.0, "Wave+ Breather? Boss")
+                .rule("Level", 1.0, "Wave* Boss");
+        for seed in 0..20 {
+            let a = grammar.generate("Level", seed).unwrap().tree.to_string();
+            let b = grammar.generate("Level", seed).unwrap().tree.to_string();
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn alternatives_are_picked_by_weight() {
+        let grammar = terminals()
+            .rule("Level", 3.0, "Wave")
+            .rule("Level", 1.0, "Boss")
+            .rule("Level", 0.0, "Breather");
+        let mut waves = 0;
+        for seed in 0..400 {
+            let tree = grammar.generate("Level", seed).unwrap().tree;
+            match symbols(&tree)[..] {
+                ["Wave"] => waves += 1,
+                ["Boss"] => {}
+                ref other => panic!("unexpected expansion {:?}", other),
+            }
+        }
+        assert!((250..350).contains(&waves), "{} of 400 were waves", waves);
+    }
+
+    #[test]
+    fn repeats_stay_within_bounds() {
+        let grammar = terminals()
+            .rule("Level", 1.0, "Wave+ Breather? Boss*")
+            .max_repeat(3);
+        for seed in 0..100 {
+            let tree = grammar.generate("Level", seed).unwrap().tree;
+            let count = |symbol| symbols(&tree).iter().filter(|s| **s == symbol).count();
+            assert!((1..=3).contains(&count("Wave")));
+            assert!(count("Breather") <= 1);
+            assert!(count("Boss") <= 3);
+        }
+    }
+
+    #[test]
+    fn limits_and_length_are_enforced() {
+        let grammar = terminals()
+            .rule("Level", 1.0, "Wave* Boss")
+            .limit("Wave", 2, 3)
+            .total_length(0.0, 450.0);
+        for seed in 0..50 {
+            let tree = grammar.generate("Level", seed).unwrap().tree;
+            assert_eq!(symbols(&tree)[..], ["Wave", "Wave", "Boss"]);
+            assert_eq!(tree.length, 400.0);
+        }
+        let impossible = terminals().rule("Level", 1.0, "Wave").limit("Wave", 2, 4);
+        assert!(matches!(
+            impossible.generate("Level", 0),
+            Err(GrammarError::Unsatisfiable { attempts: 100 })
+        ));
+    }
+
+    #[test]
+    fn deep_recursion_retries_before_failing() {
+        let grammar = terminals()
+            .rule("Level", 1.0, "Wave Level")
+            .rule("Level", 1.0, "Boss");
+        for seed in 0..50 {
+            assert!(grammar.generate("Level", seed).is_ok());
+        }
+        let endless = terminals().rule("Level", 1.0, "Wave Level");
+        assert!(matches!(
+            endless.generate("Level", 0),
+            Err(GrammarError::RecursionLimit(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_symbols_are_reported() {
+        let grammar = terminals().rule("Level", 1.0, "Wave Mystery");
+        assert!(matches!(
+            grammar.generate("Level", 0),
+            Err(GrammarError::UnknownSymbol(symbol)) if symbol == "Mystery"
+        ));
+    }
+}
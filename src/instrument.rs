@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use crate::{Instrument, LevelPlanElement, StepInfo};
+
+/// Receives the lifecycle of one element of an instrumented plan, for
+/// `PlanTracer` and `PlanStats`. Activation and resumption are reported
+/// before the element handles them, everything else after.
+pub(crate) trait Hook<T>: Clone + Send + Sync + 'static {
+    /// The hook for the child at `index` of the observed element.
+    fn child(&self, index: usize, child: &dyn LevelPlanElement<T>) -> Self;
+
+    fn activate(&self, _element: &dyn LevelPlanElement<T>, _context: &T) {}
+    fn step(
+        &self,
+        _element: &dyn LevelPlanElement<T>,
+        _context: &T,
+        _info: &StepInfo,
+        _running: bool,
+    ) {
+    }
+    fn deactivate(&self, _element: &dyn LevelPlanElement<T>, _context: &T) {}
+    fn suspend(&self, _element: &dyn LevelPlanElement<T>, _context: &T) {}
+    fn resume(&self, _element: &dyn LevelPlanElement<T>, _context: &T) {}
+
+    /// Entered around every call into the element.
+    #[cfg(feature = "tracing")]
+    fn span(&self) -> Option<tracing::span::EnteredSpan> {
+        None
+    }
+}
+
+/// Wraps `node` and all its descendants, including the ones elements like
+/// `Call` build later, with `hook` and the hooks it derives for children.
+pub(crate) fn instrument<T: 'static, H: Hook<T>>(hook: H, node: &mut Box<dyn LevelPlanElement<T>>) {
+    for (index, child) in node.children_mut().into_iter().enumerate() {
+        instrument(hook.child(index, child.as_ref()), child);
+    }
+    let parent = hook.clone();
+    node.instrument_children(Arc::new(move |index, child| {
+        instrument(parent.child(index, child.as_ref()), child)
+    }));
+    let element = std::mem::replace(node, Box::new(crate::Nop));
+    *node = Box::new(Instrumented { element, hook });
+}
+
+pub(crate) fn child_path(parent: &str, index: usize) -> String {
+    if parent.ends_with('/') {
+        format!("{}{}", parent, index)
+    } else {
+        format!("{}/{}", parent, index)
+    }
+}
+
+struct Instrumented<T, H> {
+    element: Box<dyn LevelPlanElement<T>>,
+    hook: H,
+}
+impl<T, H: Hook<T>> LevelPlanElement<T> for Instrumented<T, H> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        #[cfg(feature = "tracing")]
+        let _span = self.hook.span();
+        let running = self.element.step(level, commands, context, info);
+        self.hook
+            .step(self.element.as_ref(), context, info, running);
+        running
+    }
+
+    fn activate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        #[cfg(feature = "tracing")]
+        let _span = self.hook.span();
+        self.hook.activate(self.element.as_ref(), context);
+        self.element.activate(level, commands, context);
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        #[cfg(feature = "tracing")]
+        let _span = self.hook.span();
+        self.element.deactivate(level, commands, context);
+        self.hook.deactivate(self.element.as_ref(), context);
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        #[cfg(feature = "tracing")]
+        let _span = self.hook.span();
+        self.element.suspend(level, commands, context);
+        self.hook.suspend(self.element.as_ref(), context);
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        #[cfg(feature = "tracing")]
+        let _span = self.hook.span();
+        self.hook.resume(self.element.as_ref(), context);
+        self.element.resume(level, commands, context);
+    }
+
+    fn reset(&mut self) {
+        self.element.reset();
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        self.element.jump_to(label, level, commands, context)
+    }
+
+    fn skip_current(&mut self, level: Entity, commands: &mut Commands, context: &mut T) -> bool {
+        self.element.skip_current(level, commands, context)
+    }
+
+    fn label_index(&self, label: &str) -> Option<usize> {
+        self.element.label_index(label)
+    }
+
+    fn enter_at(&mut self, index: Option<usize>) -> bool {
+        self.element.enter_at(index)
+    }
+
+    fn skip_to_child(
+        &mut self,
+        index: usize,
+        fast_forward: bool,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        self.element
+            .skip_to_child(index, fast_forward, level, commands, context)
+    }
+
+    fn label(&self) -> String {
+        self.element.label()
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        self.element.children()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        self.element.children_mut()
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        self.element.active_children()
+    }
+
+    fn reached_checkpoint(&self) -> Option<(&str, u64)> {
+        self.element.reached_checkpoint()
+    }
+
+    fn called_subplans(&self) -> Vec<&str> {
+        self.element.called_subplans()
+    }
+
+    fn instrument_children(&mut self, instrument: Instrument<T>) {
+        self.element.instrument_children(instrument);
+    }
+}
@@ -0,0 +1,327 @@
+use bevy::prelude::*;
+
+use crate::{LevelPlanElement, StepInfo};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InterruptMode {
+    /// Resume the preempted element once the handler completes.
+    Resume,
+    /// Drop the preempted element and complete once the handler completes.
+    Abort,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ElementState {
+    Running,
+    Suspended,
+    Stopped,
+}
+
+struct Interrupt<T> {
+    guard: Box<dyn Fn(&T) -> bool + Send + Sync + 'static>,
+    handler: Box<dyn LevelPlanElement<T>>,
+    mode: InterruptMode,
+    armed: bool,
+}
+
+/// Runs `element` but hands control to the first interrupt whose guard
+/// holds. Interrupts registered earlier have higher priority and may preempt
+/// a running handler of lower priority.
+///
+/// Guards trigger on a rising edge: once an interrupt fired, its guard has to
+/// be observed false before it can fire again, so a condition that still holds
+/// after its handler finished does not restart the handler.
+pub struct Interruptible<T> {
+    element: Box<dyn LevelPlanElement<T>>,
+    element_state: ElementState,
+    interrupts: Vec<Interrupt<T>>,
+    active: Option<usize>,
+}
+impl<T> Interruptible<T> {
+    pub fn new(element: impl LevelPlanElement<T> + 'static) -> Self {
+        Self {
+            element: Box::new(element),
+            element_state: ElementState::Stopped,
+            interrupts: Vec::new(),
+            active: None,
+        }
+    }
+
+    pub fn on(
+        self,
+        guard: impl Fn(&T) -> bool + Send + Sync + 'static,
+        handler: impl LevelPlanElement<T> + 'static,
+    ) -> Self {
+        self.interrupt(InterruptMode::Resume, guard, handler)
+    }
+
+    pub fn abort_on(
+        self,
+        guard: impl Fn(&T) -> bool + Send + Sync + 'static,
+        handler: impl LevelPlanElement<T> + 'static,
+    ) -> Self {
+        self.interrupt(InterruptMode::Abort, guard, handler)
+    }
+
+    pub fn interrupt(
+        mut self,
+        mode: InterruptMode,
+        guard: impl Fn(&T) -> bool + Send + Sync + 'static,
+        handler: impl LevelPlanElement<T> + 'static,
+    ) -> Self {
+        self.interrupts.push(Interrupt {
+            guard: Box::new(guard),
+            handler: Box::new(handler),
+            mode,
+            armed: true,
+        });
+        self
+    }
+
+    fn active_element(&mut self) -> Option<&mut Box<dyn LevelPlanElement<T>>> {
+        match self.active {
+            Some(index) => Some(&mut self.interrupts[index].handler),
+            None if self.element_state == ElementState::Running => Some(&mut self.element),
+            None => None,
+        }
+    }
+}
+impl<T> LevelPlanElement<T> for Interruptible<T> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        let candidates = self.active.unwrap_or(self.interrupts.len());
+        let mut triggered = None;
+        for (index, interrupt) in self.interrupts.iter_mut().enumerate() {
+            if !(interrupt.guard)(context) {
+                interrupt.armed = true;
+            } else if interrupt.armed && index < candidates && triggered.is_none() {
+                triggered = Some(index);
+            }
+        }
+        if let Some(index) = triggered {
+            self.interrupts[index].armed = false;
+            if let Some(current) = self.active {
+                self.interrupts[current]
+                    .handler
+                    .deactivate(level, commands, context);
+            }
+            match (self.interrupts[index].mode, self.element_state) {
+                (InterruptMode::Resume, ElementState::Running) => {
+                    self.element.suspend(level, commands, context);
+                    self.element_state = ElementState::Suspended;
+                }
+                (InterruptMode::Abort, ElementState::Running)
+                | (InterruptMode::Abort, ElementState::Suspended) => {
+                    self.element.deactivate(level, commands, context);
+                    self.element_state = ElementState::Stopped;
+                }
+                _ => {}
+            }
+            self.interrupts[index]
+                .handler
+                .activate(level, commands, context);
+            self.active = Some(index);
+            return true;
+        }
+
+        match self.active {
+            Some(current) => {
+                if self.interrupts[current]
+                    .handler
+                    .step(level, commands, context, info)
+                {
+                    return true;
+                }
+                self.interrupts[current]
+                    .handler
+                    .deactivate(level, commands, context);
+                self.active = None;
+                if self.element_state == ElementState::Suspended {
+                    self.element.resume(level, commands, context);
+                    self.element_state = ElementState::Running;
+                    true
+                } else {
+                    false
+                }
+            }
+            None if self.element_state == ElementState::Running => {
+                self.element.step(level, commands, context, info)
+            }
+            None => false,
+        }
+    }
+
+    fn activate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.active = None;
+        for interrupt in self.interrupts.iter_mut() {
+            interrupt.armed = true;
+        }
+        self.element.activate(level, commands, context);
+        self.element_state = ElementState::Running;
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(current) = self.active.take() {
+            self.interrupts[current]
+                .handler
+                .deactivate(level, commands, context);
+        }
+        if self.element_state != ElementState::Stopped {
+            self.element.deactivate(level, commands, context);
+        }
+        self.element_state = ElementState::Stopped;
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(element) = self.active_element() {
+            element.suspend(level, commands, context);
+        }
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(element) = self.active_element() {
+            element.resume(level, commands, context);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.element_state = ElementState::Stopped;
+        self.active = None;
+        for interrupt in self.interrupts.iter_mut() {
+            interrupt.armed = true;
+        }
+        for child in self.children_mut() {
+            child.reset();
+        }
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        if let Some(element) = self.active_element() {
+            element.jump_to(label, level, commands, context)
+        } else {
+            false
+        }
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        std::iter::once(self.element.as_ref())
+            .chain(
+                self.interrupts
+                    .iter()
+                    .map(|interrupt| interrupt.handler.as_ref()),
+            )
+            .collect()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        std::iter::once(&mut self.element)
+            .chain(
+                self.interrupts
+                    .iter_mut()
+                    .map(|interrupt| &mut interrupt.handler),
+            )
+            .collect()
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        match self.active {
+            Some(index) => vec![index + 1],
+            None if self.element_state == ElementState::Running => vec![0],
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{activate, deactivate, step, Log};
+
+    #[derive(Default)]
+    struct Flags {
+        pause: bool,
+        abort: bool,
+    }
+
+    fn interruptible(log: &Log) -> Interruptible<Flags> {
+        Interruptible::new(log.probe("main", 100))
+            .abort_on(|flags: &Flags| flags.abort, log.probe("abort", 1))
+            .on(|flags: &Flags| flags.pause, log.probe("pause", 100))
+    }
+
+    #[test]
+    fn abort_during_resume_handler_deactivates_suspended_element() {
+        let log = Log::default();
+        let mut element = interruptible(&log);
+        let mut flags = Flags::default();
+        activate(&mut element, &mut flags);
+        flags.pause = true;
+        assert!(step(&mut element, &mut flags));
+        assert_eq!(
+            log.take(),
+            ["main activate", "main suspend", "pause activate"]
+        );
+        flags.abort = true;
+        assert!(step(&mut element, &mut flags));
+        assert_eq!(
+            log.take(),
+            ["pause deactivate", "main deactivate", "abort activate"]
+        );
+        assert!(!step(&mut element, &mut flags));
+        assert_eq!(log.take(), ["abort step", "abort deactivate"]);
+    }
+
+    #[test]
+    fn deactivate_reaches_suspended_element() {
+        let log = Log::default();
+        let mut element = interruptible(&log);
+        let mut flags = Flags::default();
+        activate(&mut element, &mut flags);
+        flags.pause = true;
+        step(&mut element, &mut flags);
+        log.take();
+        deactivate(&mut element, &mut flags);
+        assert_eq!(log.take(), ["pause deactivate", "main deactivate"]);
+    }
+
+    #[test]
+    fn guards_rearm_once_they_were_false() {
+        let log = Log::default();
+        let mut element = Interruptible::new(log.probe("main", 100))
+            .on(|flags: &Flags| flags.pause, log.probe("pause", 1));
+        let mut flags = Flags::default();
+        activate(&mut element, &mut flags);
+        flags.pause = true;
+        step(&mut element, &mut flags);
+        step(&mut element, &mut flags);
+        step(&mut element, &mut flags);
+        assert_eq!(
+            log.take(),
+            [
+                "main activate",
+                "main suspend",
+                "pause activate",
+                "pause step",
+                "pause deactivate",
+                "main resume",
+                "main step",
+            ]
+        );
+        flags.pause = false;
+        step(&mut element, &mut flags);
+        flags.pause = true;
+        step(&mut element, &mut flags);
+        assert_eq!(log.take(), ["main step", "main suspend", "pause activate"]);
+    }
+}
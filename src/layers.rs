@@ -0,0 +1,293 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{
+    restore_blackboard, run_plan, swap_blackboard, Blackboard, LevelContext, LevelPlan, PlanStats,
+    PlanTracer,
+};
+
+struct Layer<T> {
+    name: String,
+    priority: i32,
+    exclusive: bool,
+    plan: LevelPlan<T>,
+    suspended: bool,
+    stopping: bool,
+}
+
+/// Several named plans running on the same entity, each with its own
+/// lifecycle. A plan which completes is deactivated and removed, and the
+/// entity is despawned once none are left.
+///
+/// Plans are stepped in order of descending priority, so higher priority
+/// plans see the blackboard first. While an exclusive plan runs every plan
+/// of lower priority is suspended, and resumed once it completes or stops.
+pub struct LevelPlans<T> {
+    layers: Vec<Layer<T>>,
+}
+impl<T> Default for LevelPlans<T> {
+    fn default() -> Self {
+        Self { layers: Vec::new() }
+    }
+}
+impl<T> LevelPlans<T> {
+    pub fn with(mut self, name: impl Into<String>, priority: i32, plan: LevelPlan<T>) -> Self {
+        self.insert(name, priority, plan);
+        self
+    }
+
+    pub fn with_exclusive(
+        mut self,
+        name: impl Into<String>,
+        priority: i32,
+        plan: LevelPlan<T>,
+    ) -> Self {
+        self.insert_exclusive(name, priority, plan);
+        self
+    }
+
+    /// Adds a plan, stopping any plan already running under the same name.
+    pub fn insert(&mut self, name: impl Into<String>, priority: i32, plan: LevelPlan<T>) {
+        self.add(name.into(), priority, false, plan);
+    }
+
+    pub fn insert_exclusive(&mut self, name: impl Into<String>, priority: i32, plan: LevelPlan<T>) {
+        self.add(name.into(), priority, true, plan);
+    }
+
+    /// Deactivates and removes the named plan on the next run of
+    /// `level_plan_system`.
+    pub fn stop(&mut self, name: &str) {
+        if let Some(layer) = self.layer_mut(name) {
+            layer.stopping = true;
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LevelPlan<T>> {
+        self.layers
+            .iter()
+            .find(|layer| !layer.stopping && layer.name == name)
+            .map(|layer| &layer.plan)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut LevelPlan<T>> {
+        self.layer_mut(name).map(|layer| &mut layer.plan)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.layers
+            .iter()
+            .filter(|layer| !layer.stopping)
+            .map(|layer| layer.name.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.iter().all(|layer| layer.stopping)
+    }
+
+    fn add(&mut self, name: String, priority: i32, exclusive: bool, plan: LevelPlan<T>) {
+        self.stop(&name);
+        let index = self
+            .layers
+            .iter()
+            .position(|layer| layer.priority < priority)
+            .unwrap_or(self.layers.len());
+        self.layers.insert(
+            index,
+            Layer {
+                name,
+                priority,
+                exclusive,
+                plan,
+                suspended: false,
+                stopping: false,
+            },
+        );
+    }
+
+    fn layer_mut(&mut self, name: &str) -> Option<&mut Layer<T>> {
+        self.layers
+            .iter_mut()
+            .find(|layer| !layer.stopping && layer.name == name)
+    }
+}
+
+pub(crate) fn run_layered_plans<T: LevelContext + 'static>(
+    world: &mut World,
+    commands: &mut Commands,
+    context: &mut T,
+    tracer: Option<&PlanTracer>,
+    stats: Option<&PlanStats>,
+    delta: Duration,
+) {
+    for (entity, mut plans, mut blackboard) in &mut world
+        .query_mut::<(Entity, &mut LevelPlans<T>, Option<&mut Blackboard>)>()
+        .iter()
+    {
+        swap_blackboard(context, blackboard.as_deref_mut());
+        for layer in plans.layers.iter_mut() {
+            // Suspended layers too, so what their elements spawned or hold is
+            // cleaned up rather than dropped with the plan.
+            if layer.stopping && layer.plan.activated {
+                layer.plan.plan.deactivate(entity, commands, context);
+            }
+        }
+        plans.layers.retain(|layer| !layer.stopping);
+
+        let mut blocked = false;
+        for layer in plans.layers.iter_mut() {
+            if let (Some(tracer), false) = (tracer, layer.plan.traced) {
+                tracer.instrument(entity, format!("{}:/", layer.name), &mut layer.plan.plan);
+                layer.plan.traced = true;
+            }
+            if let (Some(stats), false) = (stats, layer.plan.measured) {
+                stats.instrument(format!("{}:/", layer.name), &mut layer.plan.plan);
+                layer.plan.measured = true;
+            }
+            if blocked {
+                if layer.plan.activated && !layer.suspended {
+                    layer.plan.plan.suspend(entity, commands, context);
+                    layer.suspended = true;
+                }
+                continue;
+            }
+            if layer.suspended {
+                layer.plan.plan.resume(entity, commands, context);
+                layer.suspended = false;
+            }
+            if run_plan(&mut layer.plan, entity, commands, context, delta) {
+                blocked |= layer.exclusive;
+            } else {
+                layer.plan.plan.deactivate(entity, commands, context);
+                layer.stopping = true;
+            }
+        }
+        plans.layers.retain(|layer| !layer.stopping);
+        restore_blackboard(context, entity, blackboard.as_deref_mut(), commands);
+        if plans.layers.is_empty() {
+            commands.despawn(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{run, Context, Log, Probe},
+        Sequence,
+    };
+
+    fn plan(probe: Probe) -> LevelPlan<Context> {
+        LevelPlan::new(probe)
+    }
+
+    fn plans(world: &mut World, entity: Entity) -> Mut<'_, LevelPlans<Context>> {
+        world.get_mut::<LevelPlans<Context>>(entity).unwrap()
+    }
+
+    #[test]
+    fn higher_priority_layers_step_first() {
+        let log = Log::default();
+        let mut world = World::new();
+        world.spawn((LevelPlans::default()
+            .with("low", 0, plan(log.probe("low", 100)))
+            .with("high", 10, plan(log.probe("high", 100))),));
+        run(&mut world);
+        assert_eq!(
+            log.take(),
+            ["high activate", "high step", "low activate", "low step"]
+        );
+    }
+
+    #[test]
+    fn exclusive_layers_suspend_lower_ones() {
+        let log = Log::default();
+        let mut world = World::new();
+        let entity =
+            world.spawn((LevelPlans::default().with("main", 0, plan(log.probe("main", 100))),));
+        run(&mut world);
+        plans(&mut world, entity).insert_exclusive("cutscene", 10, plan(log.probe("cutscene", 2)));
+        run(&mut world);
+        run(&mut world);
+        run(&mut world);
+        assert_eq!(
+            log.take(),
+            [
+                "main activate",
+                "main step",
+                "cutscene activate",
+                "cutscene step",
+                "main suspend",
+                "cutscene step",
+                "cutscene deactivate",
+                "main resume",
+                "main step",
+                "main step",
+            ]
+        );
+    }
+
+    #[test]
+    fn stopping_a_suspended_layer_deactivates_it() {
+        let log = Log::default();
+        let mut world = World::new();
+        let entity =
+            world.spawn((LevelPlans::default().with("main", 0, plan(log.probe("main", 100))),));
+        run(&mut world);
+        plans(&mut world, entity).insert_exclusive(
+            "cutscene",
+            10,
+            plan(log.probe("cutscene", 100)),
+        );
+        run(&mut world);
+        log.take();
+        plans(&mut world, entity).stop("main");
+        run(&mut world);
+        assert_eq!(log.take(), ["main deactivate", "cutscene step"]);
+        assert!(!plans(&mut world, entity).contains("main"));
+    }
+
+    #[test]
+    fn layers_rewind_to_their_own_checkpoints() {
+        let log = Log::default();
+        let mut world = World::new();
+        let entity = world.spawn((LevelPlans::default()
+            .with(
+                "first",
+                10,
+                LevelPlan::<Context>::new(
+                    Sequence::default()
+                        .push(log.probe("intro", 1))
+                        .checkpoint("arena")
+                        .push(log.probe("arena", 100)),
+                ),
+            )
+            .with(
+                "second",
+                0,
+                LevelPlan::new(
+                    Sequence::default()
+                        .checkpoint("side")
+                        .push(log.probe("side", 100)),
+                ),
+            ),));
+        run(&mut world);
+        run(&mut world);
+        log.take();
+        plans(&mut world, entity)
+            .get_mut("first")
+            .unwrap()
+            .rewind_to_checkpoint();
+        run(&mut world);
+        assert_eq!(
+            log.take(),
+            ["arena deactivate", "arena activate", "side step"]
+        );
+    }
+}
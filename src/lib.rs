@@ -1,58 +1,450 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
 use bevy::prelude::*;
 
+mod app_state;
+pub use app_state::*;
+mod blackboard;
+pub use blackboard::*;
+mod closure;
+pub use closure::*;
+mod coroutine;
+pub use coroutine::*;
+mod director;
+pub use director::*;
+mod edge;
+pub use edge::*;
+mod export;
+pub use export::*;
+mod grammar;
+pub use grammar::*;
+mod library;
+pub use library::*;
+mod region;
+pub use region::*;
+#[cfg(feature = "replay")]
+mod replay;
+#[cfg(feature = "replay")]
+pub use replay::*;
+mod instrument;
+mod interrupt;
+pub use interrupt::*;
+mod layers;
+pub use layers::*;
+mod signal;
+pub use signal::*;
+mod skip;
+mod state_machine;
+pub use state_machine::*;
+mod stats;
+pub use stats::*;
+mod switch;
+pub use switch::*;
+#[cfg(test)]
+mod testing;
+mod timeout;
+pub use timeout::*;
+mod timestep;
+pub use timestep::*;
+mod trace;
+pub use trace::*;
+mod wave;
+pub use wave::*;
+
 pub trait LevelContext {
     fn build(world: &World, resources: &Resources) -> Self;
+
+    fn blackboard(&self) -> Option<&Blackboard> {
+        None
+    }
+    fn blackboard_mut(&mut self) -> Option<&mut Blackboard> {
+        None
+    }
+
+    /// The translations `Tracker::entity` and `Tracker::marker` read from.
+    fn tracked_transforms(&self) -> Option<&TrackedTransforms> {
+        None
+    }
+
+    /// A short description of the context included in `PlanEvent`s.
+    fn summary(&self) -> String {
+        String::new()
+    }
 }
 pub fn level_plan_system<T: LevelContext + 'static>(
     world: &mut World,
     resources: &mut Resources,
 ) {
-    let mut context = T::build(world, resources);
+    let tracer = resources.get::<PlanTracer>().map(|tracer| PlanTracer::clone(&tracer));
+    for delta in frame_steps::<T>(resources) {
+        let mut context = T::build(world, resources);
+        run_level_plans(world, resources, &mut context, tracer.as_ref(), delta);
+    }
+}
+
+/// Steps every `LevelPlan<T>` in the world once against `context`, advancing
+/// their clocks by `delta`.
+pub(crate) fn run_level_plans<T: LevelContext + 'static>(
+    world: &mut World,
+    resources: &mut Resources,
+    context: &mut T,
+    tracer: Option<&PlanTracer>,
+    delta: Duration,
+) {
     let mut command_buffer = Commands::default();
     command_buffer.set_entity_reserver(world.get_entity_reserver());
-    for (entity, mut plan) in &mut world.query_mut::<(Entity, &mut LevelPlan<T>)>().iter() {
-        if !plan.activated {
-            plan.plan
-                .activate(entity, &mut command_buffer, &mut context);
-            plan.activated = true;
+    if let Some(tracer) = tracer {
+        tracer.next_frame();
+    }
+    let stats = resources.get::<PlanStats>().map(|stats| PlanStats::clone(&stats));
+    for (entity, mut plan, mut blackboard) in &mut world
+        .query_mut::<(Entity, &mut LevelPlan<T>, Option<&mut Blackboard>)>()
+        .iter()
+    {
+        swap_blackboard(context, blackboard.as_deref_mut());
+        if let (Some(tracer), false) = (tracer, plan.traced) {
+            tracer.instrument(entity, "/".to_string(), &mut plan.plan);
+            plan.traced = true;
+        }
+        if let (Some(stats), false) = (&stats, plan.measured) {
+            stats.instrument("/".to_string(), &mut plan.plan);
+            plan.measured = true;
         }
-        if !plan.plan.step(entity, &mut command_buffer, &mut context) {
+        let running = run_plan(&mut plan, entity, &mut command_buffer, context, delta);
+        restore_blackboard(context, entity, blackboard.as_deref_mut(), &mut command_buffer);
+        if !running {
             command_buffer.despawn(entity);
         }
     }
+    run_layered_plans(world, &mut command_buffer, context, tracer, stats.as_ref(), delta);
     command_buffer.apply(world, resources);
 }
 
+/// Activates the plan on its first run, applies any pending jump and steps
+/// it. Returns whether the plan is still running.
+pub(crate) fn run_plan<T>(
+    plan: &mut LevelPlan<T>,
+    entity: Entity,
+    commands: &mut Commands,
+    context: &mut T,
+    delta: Duration,
+) -> bool {
+    if std::mem::take(&mut plan.restart) {
+        if plan.activated {
+            plan.plan.deactivate(entity, commands, context);
+            plan.activated = false;
+        }
+        plan.plan.reset();
+        plan.pending_jump = None;
+        plan.elapsed = Duration::from_secs(0);
+        plan.frame = 0;
+    }
+    if !plan.activated {
+        plan.plan.activate(entity, commands, context);
+        plan.activated = true;
+    } else if let Some(jump) = plan.pending_jump.take() {
+        match jump {
+            PendingJump::Label(label) => {
+                if !skip::jump_to_label(&mut plan.plan, &label, entity, commands, context) {
+                    plan.plan.deactivate(entity, commands, context);
+                    plan.plan.activate(entity, commands, context);
+                }
+            }
+            PendingJump::Checkpoint => {
+                let jumped = last_checkpoint(plan.plan.as_ref()).is_some_and(|label| {
+                    skip::jump_to_label(&mut plan.plan, &label, entity, commands, context)
+                });
+                if !jumped {
+                    plan.plan.deactivate(entity, commands, context);
+                    plan.plan.activate(entity, commands, context);
+                }
+            }
+            PendingJump::SkipCurrent => {
+                skip::skip_current(&mut plan.plan, entity, commands, context);
+            }
+            PendingJump::SkipTo(path) => {
+                skip::skip_to(
+                    &mut plan.plan,
+                    &path,
+                    plan.fast_forward,
+                    entity,
+                    commands,
+                    context,
+                );
+            }
+        }
+    }
+    let info = plan.advance(delta);
+    plan.plan.step(entity, commands, context, &info)
+}
+
+/// The label of the `Checkpoint` in `element`'s tree which was activated
+/// most recently.
+fn last_checkpoint<T>(element: &dyn LevelPlanElement<T>) -> Option<String> {
+    fn latest<T>(element: &dyn LevelPlanElement<T>) -> Option<(&str, u64)> {
+        element
+            .children()
+            .into_iter()
+            .filter_map(latest)
+            .chain(element.reached_checkpoint())
+            .max_by_key(|(_, reached)| *reached)
+    }
+    latest(element).map(|(label, _)| label.to_string())
+}
+
+pub(crate) fn swap_blackboard<T: LevelContext>(
+    context: &mut T,
+    blackboard: Option<&mut Blackboard>,
+) {
+    if let (Some(a), Some(b)) = (context.blackboard_mut(), blackboard) {
+        std::mem::swap(a, b);
+    }
+}
+
+/// Swaps the plan's blackboard back out of the context after stepping. An
+/// entity without a `Blackboard` is given the context's one, which would
+/// otherwise be thrown away with the context at the end of the frame.
+pub(crate) fn restore_blackboard<T: LevelContext>(
+    context: &mut T,
+    entity: Entity,
+    blackboard: Option<&mut Blackboard>,
+    commands: &mut Commands,
+) {
+    match blackboard {
+        Some(blackboard) => swap_blackboard(context, Some(blackboard)),
+        None => {
+            if let Some(blackboard) = context.blackboard_mut() {
+                commands.insert_one(entity, std::mem::take(blackboard));
+            }
+        }
+    }
+}
+
+/// Timing of a step, passed to `LevelPlanElement::step`. Each plan keeps its
+/// own clock, which only advances while the plan is being stepped.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StepInfo {
+    /// Time since the plan's previous step, zero on its first step.
+    pub delta: Duration,
+    /// Time since the plan was activated, including `delta`.
+    pub elapsed: Duration,
+    /// Number of steps the plan took before this one.
+    pub frame: u64,
+}
+impl StepInfo {
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+}
+
+enum PendingJump {
+    Label(String),
+    Checkpoint,
+    SkipCurrent,
+    SkipTo(Vec<usize>),
+}
+
 pub struct LevelPlan<T> {
     plan: Box<dyn LevelPlanElement<T>>,
     activated: bool,
+    traced: bool,
+    measured: bool,
+    fast_forward: bool,
+    pending_jump: Option<PendingJump>,
+    restart: bool,
+    elapsed: Duration,
+    frame: u64,
 }
 impl<T> LevelPlan<T> {
     pub fn new(element: impl LevelPlanElement<T> + 'static) -> Self {
         Self {
             plan: Box::new(element),
             activated: false,
+            traced: false,
+            measured: false,
+            fast_forward: false,
+            pending_jump: None,
+            restart: false,
+            elapsed: Duration::from_secs(0),
+            frame: 0,
+        }
+    }
+
+    /// Moves execution to a `Sequence` label on the next run of
+    /// `level_plan_system`. Sequences along the active branch are searched
+    /// first, then the whole plan, which every element on the way to the
+    /// label must be able to skip to. If that fails the plan restarts from
+    /// the beginning. Returns `false` without doing anything if no element of
+    /// the plan defines the label.
+    pub fn jump_to(&mut self, label: impl Into<String>) -> bool {
+        let label = label.into();
+        if skip::find_label(self.plan.as_ref(), &label).is_none() {
+            return false;
         }
+        self.pending_jump = Some(PendingJump::Label(label));
+        true
+    }
+
+    /// Jumps back to the most recently activated `Checkpoint` like `jump_to`,
+    /// or restarts the plan from the beginning if none has been reached yet.
+    pub fn rewind_to_checkpoint(&mut self) {
+        self.pending_jump = Some(PendingJump::Checkpoint);
+    }
+
+    /// Deactivates the plan, resets every element and starts it again from
+    /// the beginning on the next run of `level_plan_system`. Unlike
+    /// `rewind_to_checkpoint` this also forgets the checkpoints reached.
+    pub fn restart(&mut self) {
+        self.restart = true;
+    }
+
+    fn advance(&mut self, delta: Duration) -> StepInfo {
+        let delta = if self.frame == 0 {
+            Duration::from_secs(0)
+        } else {
+            delta
+        };
+        self.elapsed += delta;
+        let info = StepInfo {
+            delta,
+            elapsed: self.elapsed,
+            frame: self.frame,
+        };
+        self.frame += 1;
+        info
     }
 }
 
 pub trait LevelPlanElement<T>: Send + Sync {
-    fn step(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) -> bool {
+    fn step(
+        &mut self,
+        _level: Entity,
+        _commands: &mut Commands,
+        _context: &mut T,
+        _info: &StepInfo,
+    ) -> bool {
         true
     }
     fn activate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {}
     fn deactivate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {}
+    /// Like `deactivate` but the element should keep enough state to pick up
+    /// where it left off in `resume`.
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.deactivate(level, commands, context);
+    }
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.activate(level, commands, context);
+    }
+    /// Returns the element to the state it was built in, so the next
+    /// `activate` starts it afresh. Only called while the element isn't running.
+    fn reset(&mut self) {
+        for child in self.children_mut() {
+            child.reset();
+        }
+    }
+    fn jump_to(
+        &mut self,
+        _label: &str,
+        _level: Entity,
+        _commands: &mut Commands,
+        _context: &mut T,
+    ) -> bool {
+        false
+    }
+    /// Finishes the running child as if it had completed. Returns `false` if
+    /// the element has no notion of a current child to skip.
+    fn skip_current(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) -> bool {
+        false
+    }
+    /// The index into `children` of the child `label` refers to, for
+    /// elements such as `Sequence` which define labels.
+    fn label_index(&self, _label: &str) -> Option<usize> {
+        None
+    }
+    /// Makes the next `activate` start at the child at `index` instead of
+    /// the first one, or at the first one again with `None`. Returns `false`
+    /// if the element can't start elsewhere.
+    fn enter_at(&mut self, _index: Option<usize>) -> bool {
+        false
+    }
+    /// Makes the child at `index` into `children` the running one. With
+    /// `fast_forward` every child passed over on the way is activated and
+    /// deactivated without being stepped. Returns `false` if the element
+    /// can't move to that child.
+    fn skip_to_child(
+        &mut self,
+        _index: usize,
+        _fast_forward: bool,
+        _level: Entity,
+        _commands: &mut Commands,
+        _context: &mut T,
+    ) -> bool {
+        false
+    }
+    /// A short description of the element, used when tracing and exporting
+    /// plans.
+    fn label(&self) -> String {
+        short_type_name(std::any::type_name::<Self>())
+    }
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        Vec::new()
+    }
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        Vec::new()
+    }
+    /// Indices into `children` of the children that are currently running.
+    fn active_children(&self) -> Vec<usize> {
+        Vec::new()
+    }
+    /// The label of a `Checkpoint` and when it was last activated, increasing
+    /// across all checkpoints.
+    fn reached_checkpoint(&self) -> Option<(&str, u64)> {
+        None
+    }
+    /// The names of the `PlanLibrary` templates the element itself calls, for
+    /// `PlanLibrary::validate_plan`.
+    fn called_subplans(&self) -> Vec<&str> {
+        Vec::new()
+    }
+    /// Keeps `instrument` to apply to every child created while the plan
+    /// runs. Only elements which build children late, like `Call`, need it.
+    fn instrument_children(&mut self, _instrument: Instrument<T>) {}
+}
+
+/// Wraps a child an element built while running, such as the subplan a
+/// `Call` instantiates, the way a `PlanTracer` or `PlanStats` wrapped the
+/// rest of the plan. Takes the index of the child in `children`.
+pub type Instrument<T> = Arc<dyn Fn(usize, &mut Box<dyn LevelPlanElement<T>>) + Send + Sync>;
+
+fn short_type_name(name: &str) -> String {
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name).to_string()
 }
 
 pub struct Sequence<T> {
     index: usize,
+    entry: Option<usize>,
     elements: Vec<Box<dyn LevelPlanElement<T>>>,
+    labels: HashMap<String, usize>,
 }
 impl<T> Default for Sequence<T> {
     fn default() -> Self {
         Self {
             index: 0,
+            entry: None,
             elements: Vec::new(),
+            labels: HashMap::new(),
         }
     }
 }
@@ -61,11 +453,32 @@ impl<T> Sequence<T> {
         self.elements.push(Box::new(element));
         self
     }
+
+    pub fn push_boxed(mut self, element: Box<dyn LevelPlanElement<T>>) -> Self {
+        self.elements.push(element);
+        self
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.labels.insert(label.into(), self.elements.len());
+        self
+    }
+
+    pub fn checkpoint(self, label: impl Into<String>) -> Self {
+        let label = label.into();
+        self.label(label.clone()).push(Checkpoint::new(label))
+    }
 }
 impl<T> LevelPlanElement<T> for Sequence<T> {
-    fn step(&mut self, level: Entity, commands: &mut Commands, context: &mut T) -> bool {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
         if let Some(element) = self.elements.get_mut(self.index) {
-            if !element.step(level, commands, context) {
+            if !element.step(level, commands, context, info) {
                 self.elements[self.index].deactivate(level, commands, context);
                 self.index += 1;
                 if self.index < self.elements.len() {
@@ -83,13 +496,168 @@ impl<T> LevelPlanElement<T> for Sequence<T> {
     }
 
     fn activate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
-        self.index = 0;
-        self.elements[0].activate(level, commands, context);
+        self.index = self.entry.take().unwrap_or(0);
+        if let Some(element) = self.elements.get_mut(self.index) {
+            element.activate(level, commands, context);
+        }
     }
 
     fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
-        self.elements[self.index].deactivate(level, commands, context);
+        if let Some(element) = self.elements.get_mut(self.index) {
+            element.deactivate(level, commands, context);
+        }
+        self.index = 0;
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(element) = self.elements.get_mut(self.index) {
+            element.suspend(level, commands, context);
+        }
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(element) = self.elements.get_mut(self.index) {
+            element.resume(level, commands, context);
+        }
+    }
+
+    fn reset(&mut self) {
         self.index = 0;
+        self.entry = None;
+        for child in self.children_mut() {
+            child.reset();
+        }
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        match self.labels.get(label) {
+            Some(&index) if index < self.elements.len() => {
+                if let Some(element) = self.elements.get_mut(self.index) {
+                    element.deactivate(level, commands, context);
+                }
+                self.index = index;
+                self.elements[index].activate(level, commands, context);
+                true
+            }
+            _ => {
+                if let Some(element) = self.elements.get_mut(self.index) {
+                    element.jump_to(label, level, commands, context)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    fn skip_current(&mut self, level: Entity, commands: &mut Commands, context: &mut T) -> bool {
+        self.skip_to_child(self.index + 1, false, level, commands, context)
+    }
+
+    fn label_index(&self, label: &str) -> Option<usize> {
+        self.labels.get(label).copied()
+    }
+
+    fn enter_at(&mut self, index: Option<usize>) -> bool {
+        if index.is_some_and(|index| index > self.elements.len()) {
+            return false;
+        }
+        self.entry = index;
+        true
+    }
+
+    fn skip_to_child(
+        &mut self,
+        index: usize,
+        fast_forward: bool,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        if index > self.elements.len() {
+            return false;
+        }
+        if let Some(element) = self.elements.get_mut(self.index) {
+            element.deactivate(level, commands, context);
+        }
+        if fast_forward {
+            for element in self.elements.iter_mut().take(index).skip(self.index + 1) {
+                element.activate(level, commands, context);
+                element.deactivate(level, commands, context);
+            }
+        }
+        self.index = index;
+        if let Some(element) = self.elements.get_mut(index) {
+            element.activate(level, commands, context);
+        }
+        true
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        self.elements.iter().map(|element| element.as_ref()).collect()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        self.elements.iter_mut().collect()
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        if self.index < self.elements.len() {
+            vec![self.index]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Orders checkpoint activations across all plans.
+static CHECKPOINTS_REACHED: AtomicU64 = AtomicU64::new(0);
+
+/// Marks the point `LevelPlan::rewind_to_checkpoint` returns to once
+/// activated. Each plan, and each layer of a `LevelPlans`, only rewinds to
+/// checkpoints in its own tree.
+pub struct Checkpoint {
+    label: String,
+    reached: Option<u64>,
+}
+impl Checkpoint {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            reached: None,
+        }
+    }
+}
+impl<T> LevelPlanElement<T> for Checkpoint {
+    fn step(
+        &mut self,
+        _level: Entity,
+        _commands: &mut Commands,
+        _context: &mut T,
+        _info: &StepInfo,
+    ) -> bool {
+        false
+    }
+
+    fn activate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {
+        self.reached = Some(CHECKPOINTS_REACHED.fetch_add(1, Ordering::Relaxed));
+    }
+
+    fn reset(&mut self) {
+        self.reached = None;
+    }
+
+    fn label(&self) -> String {
+        format!("Checkpoint({})", self.label)
+    }
+
+    fn reached_checkpoint(&self) -> Option<(&str, u64)> {
+        self.reached.map(|reached| (self.label.as_str(), reached))
     }
 }
 
@@ -109,9 +677,15 @@ impl<T> While<T> {
     }
 }
 impl<T> LevelPlanElement<T> for While<T> {
-    fn step(&mut self, level: Entity, commands: &mut Commands, context: &mut T) -> bool {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
         if (self.condition)(context) {
-            self.element.step(level, commands, context)
+            self.element.step(level, commands, context, info)
         } else {
             false
         }
@@ -124,6 +698,36 @@ impl<T> LevelPlanElement<T> for While<T> {
     fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
         self.element.deactivate(level, commands, context);
     }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.element.suspend(level, commands, context);
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.element.resume(level, commands, context);
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        self.element.jump_to(label, level, commands, context)
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        vec![self.element.as_ref()]
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        vec![&mut self.element]
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        vec![0]
+    }
 }
 
 pub struct Cycle<T> {
@@ -135,8 +739,14 @@ impl<T> Cycle<T> {
     }
 }
 impl<T> LevelPlanElement<T> for Cycle<T> {
-    fn step(&mut self, level: Entity, commands: &mut Commands, context: &mut T) -> bool {
-        if !self.sequence.step(level, commands, context) {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        if !self.sequence.step(level, commands, context, info) {
             self.sequence.activate(level, commands, context);
         }
         true
@@ -149,6 +759,64 @@ impl<T> LevelPlanElement<T> for Cycle<T> {
     fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
         self.sequence.deactivate(level, commands, context);
     }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.sequence.suspend(level, commands, context);
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.sequence.resume(level, commands, context);
+    }
+
+    fn reset(&mut self) {
+        self.sequence.reset();
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        self.sequence.jump_to(label, level, commands, context)
+    }
+
+    fn skip_current(&mut self, level: Entity, commands: &mut Commands, context: &mut T) -> bool {
+        self.sequence.skip_current(level, commands, context)
+    }
+
+    fn label_index(&self, label: &str) -> Option<usize> {
+        self.sequence.label_index(label)
+    }
+
+    fn enter_at(&mut self, index: Option<usize>) -> bool {
+        self.sequence.enter_at(index)
+    }
+
+    fn skip_to_child(
+        &mut self,
+        index: usize,
+        fast_forward: bool,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        self.sequence
+            .skip_to_child(index, fast_forward, level, commands, context)
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        self.sequence.children()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        self.sequence.children_mut()
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        self.sequence.active_children()
+    }
 }
 
 pub struct Nop;
@@ -170,6 +838,10 @@ impl<T, C: Send + Sync + Clone + 'static> LevelPlanElement<T> for SetComponent<C
     fn deactivate(&mut self, level: Entity, commands: &mut Commands, _context: &mut T) {
         commands.remove_one::<C>(level);
     }
+
+    fn label(&self) -> String {
+        format!("SetComponent({})", short_type_name(std::any::type_name::<C>()))
+    }
 }
 
 pub struct Conditional<T> {
@@ -208,7 +880,13 @@ impl<T> Conditional<T> {
     }
 }
 impl<T> LevelPlanElement<T> for Conditional<T> {
-    fn step(&mut self, level: Entity, commands: &mut Commands, context: &mut T) -> bool {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
         if (self.condition)(context) {
             if !self.if_active {
                 self.if_branch.activate(level, commands, context);
@@ -220,7 +898,7 @@ impl<T> LevelPlanElement<T> for Conditional<T> {
                 }
                 self.else_active = false;
             }
-            self.if_branch.step(level, commands, context)
+            self.if_branch.step(level, commands, context, info)
         } else {
             if self.if_active {
                 self.if_branch.deactivate(level, commands, context);
@@ -231,7 +909,7 @@ impl<T> LevelPlanElement<T> for Conditional<T> {
                     else_branch.activate(level, commands, context);
                     self.else_active = true;
                 }
-                else_branch.step(level, commands, context)
+                else_branch.step(level, commands, context, info)
             } else {
                 false
             }
@@ -250,4 +928,128 @@ impl<T> LevelPlanElement<T> for Conditional<T> {
             self.else_active = false;
         }
     }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if self.if_active {
+            self.if_branch.suspend(level, commands, context);
+        } else if let (true, Some(else_branch)) = (self.else_active, self.else_branch.as_mut()) {
+            else_branch.suspend(level, commands, context);
+        }
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if self.if_active {
+            self.if_branch.resume(level, commands, context);
+        } else if let (true, Some(else_branch)) = (self.else_active, self.else_branch.as_mut()) {
+            else_branch.resume(level, commands, context);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.if_active = false;
+        self.else_active = false;
+        for child in self.children_mut() {
+            child.reset();
+        }
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        if self.if_active {
+            self.if_branch.jump_to(label, level, commands, context)
+        } else if let (true, Some(else_branch)) = (self.else_active, self.else_branch.as_mut()) {
+            else_branch.jump_to(label, level, commands, context)
+        } else {
+            false
+        }
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        std::iter::once(self.if_branch.as_ref())
+            .chain(self.else_branch.as_deref())
+            .collect()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        std::iter::once(&mut self.if_branch)
+            .chain(self.else_branch.as_mut())
+            .collect()
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        match (self.if_active, self.else_active) {
+            (true, _) => vec![0],
+            (false, true) => vec![1],
+            (false, false) => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run, Context, Log};
+
+    fn spawn(world: &mut World, log: &Log) -> Entity {
+        world.spawn((LevelPlan::<Context>::new(
+            Sequence::default()
+                .push(log.probe("intro", 2))
+                .checkpoint("arena")
+                .push(log.probe("arena", 1))
+                .push(log.probe("boss", 100)),
+        ),))
+    }
+
+    fn plan(world: &mut World, entity: Entity) -> Mut<'_, LevelPlan<Context>> {
+        world.get_mut::<LevelPlan<Context>>(entity).unwrap()
+    }
+
+    fn reach_boss(world: &mut World, log: &Log) -> Entity {
+        let entity = spawn(world, log);
+        for _ in 0..5 {
+            run(world);
+        }
+        assert_eq!(log.take().last().unwrap(), "boss step");
+        entity
+    }
+
+    #[test]
+    fn rewinding_restarts_from_the_checkpoint() {
+        let log = Log::default();
+        let mut world = World::new();
+        let entity = reach_boss(&mut world, &log);
+        plan(&mut world, entity).rewind_to_checkpoint();
+        run(&mut world);
+        run(&mut world);
+        assert_eq!(
+            log.take(),
+            [
+                "boss deactivate",
+                "arena activate",
+                "arena step",
+                "arena deactivate",
+                "boss activate",
+            ]
+        );
+        assert_eq!(plan(&mut world, entity).elapsed, Duration::from_millis(60));
+    }
+
+    #[test]
+    fn restarting_forgets_checkpoints() {
+        let log = Log::default();
+        let mut world = World::new();
+        let entity = reach_boss(&mut world, &log);
+        plan(&mut world, entity).restart();
+        run(&mut world);
+        assert_eq!(log.take(), ["boss deactivate", "intro activate", "intro step"]);
+        assert_eq!(plan(&mut world, entity).frame, 1);
+        plan(&mut world, entity).rewind_to_checkpoint();
+        run(&mut world);
+        assert_eq!(log.take(), ["intro deactivate", "intro activate", "intro step"]);
+    }
 }
@@ -0,0 +1,291 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+use bevy::prelude::*;
+
+use crate::{Blackboard, Instrument, LevelPlan, LevelPlanElement, StepInfo, VarKey};
+
+type Factory<T> =
+    Arc<dyn Fn(&PlanLibrary<T>, &Blackboard) -> Box<dyn LevelPlanElement<T>> + Send + Sync>;
+type Binding<T> = Box<dyn Fn(&T, &mut Blackboard) + Send + Sync>;
+
+struct Template<T> {
+    factory: Factory<T>,
+    calls: Vec<String>,
+}
+
+/// Named factories for subplans which are instantiated fresh by `Call`.
+///
+/// Clones share the same set of templates.
+pub struct PlanLibrary<T> {
+    templates: Arc<RwLock<HashMap<String, Template<T>>>>,
+}
+impl<T> Default for PlanLibrary<T> {
+    fn default() -> Self {
+        Self {
+            templates: Default::default(),
+        }
+    }
+}
+impl<T> Clone for PlanLibrary<T> {
+    fn clone(&self) -> Self {
+        Self {
+            templates: self.templates.clone(),
+        }
+    }
+}
+impl<T: 'static> PlanLibrary<T> {
+    pub fn register<E: LevelPlanElement<T> + 'static>(
+        self,
+        name: impl Into<String>,
+        factory: impl Fn(&PlanLibrary<T>, &Blackboard) -> E + Send + Sync + 'static,
+    ) -> Self {
+        self.register_calling(name, &[], factory)
+    }
+
+    /// Like `register` for a template whose subplan may `call` the templates
+    /// named in `calls`, which `validate` then checks as well.
+    pub fn register_calling<E: LevelPlanElement<T> + 'static>(
+        self,
+        name: impl Into<String>,
+        calls: &[&str],
+        factory: impl Fn(&PlanLibrary<T>, &Blackboard) -> E + Send + Sync + 'static,
+    ) -> Self {
+        self.templates.write().unwrap().insert(
+            name.into(),
+            Template {
+                factory: Arc::new(move |library, params| Box::new(factory(library, params))),
+                calls: calls.iter().map(|call| call.to_string()).collect(),
+            },
+        );
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.templates.read().unwrap().contains_key(name)
+    }
+
+    pub fn instantiate(
+        &self,
+        name: &str,
+        params: &Blackboard,
+    ) -> Option<Box<dyn LevelPlanElement<T>>> {
+        let factory = self
+            .templates
+            .read()
+            .unwrap()
+            .get(name)
+            .map(|template| template.factory.clone());
+        factory.map(|factory| factory(self, params))
+    }
+
+    pub fn call(&self, name: impl Into<String>) -> Call<T> {
+        Call {
+            library: self.clone(),
+            name: name.into(),
+            params: Blackboard::default(),
+            bindings: Vec::new(),
+            element: None,
+            instruments: Vec::new(),
+        }
+    }
+
+    /// Like `validate` for the names of every `Call` found in `plan`.
+    pub fn validate_plan(&self, plan: &LevelPlan<T>) -> Result<(), MissingSubplans> {
+        fn collect<'a, T>(element: &'a dyn LevelPlanElement<T>, names: &mut Vec<&'a str>) {
+            names.extend(element.called_subplans());
+            for child in element.children() {
+                collect(child, names);
+            }
+        }
+        let mut names = Vec::new();
+        collect(plan.plan.as_ref(), &mut names);
+        self.validate(&names)
+    }
+
+    /// Checks that every name in `names`, usually those the plan calls, and
+    /// every name the templates reached from them declare in
+    /// `register_calling` refers to a registered template.
+    pub fn validate(&self, names: &[&str]) -> Result<(), MissingSubplans> {
+        let templates = self.templates.read().unwrap();
+        let mut pending: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        let mut seen = HashSet::new();
+        let mut missing = Vec::new();
+        while let Some(name) = pending.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            match templates.get(&name) {
+                Some(template) => pending.extend(template.calls.iter().cloned()),
+                None => missing.push(name),
+            }
+        }
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            missing.sort();
+            Err(MissingSubplans(missing))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MissingSubplans(pub Vec<String>);
+impl fmt::Display for MissingSubplans {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "missing subplans: {}", self.0.join(", "))
+    }
+}
+impl std::error::Error for MissingSubplans {}
+
+pub struct Call<T> {
+    library: PlanLibrary<T>,
+    name: String,
+    params: Blackboard,
+    bindings: Vec<Binding<T>>,
+    element: Option<Box<dyn LevelPlanElement<T>>>,
+    instruments: Vec<Instrument<T>>,
+}
+impl<T> Call<T> {
+    pub fn with<V: Send + Sync + 'static>(mut self, key: VarKey<V>, value: V) -> Self {
+        self.params.set(key, value);
+        self
+    }
+
+    pub fn bind<V: Send + Sync + 'static>(
+        mut self,
+        key: VarKey<V>,
+        value: impl Fn(&T) -> V + Send + Sync + 'static,
+    ) -> Self {
+        self.bindings.push(Box::new(move |context, params| {
+            params.set(key, value(context))
+        }));
+        self
+    }
+}
+impl<T: 'static> LevelPlanElement<T> for Call<T> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        if let Some(element) = self.element.as_mut() {
+            element.step(level, commands, context, info)
+        } else {
+            false
+        }
+    }
+
+    fn activate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        for binding in &self.bindings {
+            binding(context, &mut self.params);
+        }
+        let mut element = match self.library.instantiate(&self.name, &self.params) {
+            Some(element) => element,
+            None => panic!(
+                "Call of unknown subplan '{}', check plans with PlanLibrary::validate_plan",
+                self.name
+            ),
+        };
+        for instrument in &self.instruments {
+            instrument(0, &mut element);
+        }
+        element.activate(level, commands, context);
+        self.element = Some(element);
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(mut element) = self.element.take() {
+            element.deactivate(level, commands, context);
+        }
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(element) = self.element.as_mut() {
+            element.suspend(level, commands, context);
+        }
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(element) = self.element.as_mut() {
+            element.resume(level, commands, context);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.element = None;
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        if let Some(element) = self.element.as_mut() {
+            element.jump_to(label, level, commands, context)
+        } else {
+            false
+        }
+    }
+
+    fn label(&self) -> String {
+        format!("Call({})", self.name)
+    }
+
+    fn called_subplans(&self) -> Vec<&str> {
+        vec![self.name.as_str()]
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        self.element.as_deref().into_iter().collect()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        self.element.iter_mut().collect()
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        self.element.iter().map(|_| 0).collect()
+    }
+
+    fn instrument_children(&mut self, instrument: Instrument<T>) {
+        self.instruments.push(instrument);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Nop, Sequence};
+
+    #[test]
+    fn validate_plan_finds_nested_calls() {
+        let library = PlanLibrary::<()>::default()
+            .register("Wave", |_, _| Nop)
+            .register_calling("Arena", &["Boss"], |library, _| library.call("Boss"));
+        let plan = LevelPlan::new(
+            Sequence::default()
+                .push(library.call("Wave"))
+                .push(Sequence::default().push(library.call("Arena")))
+                .push(library.call("Outro")),
+        );
+        let missing = library.validate_plan(&plan).unwrap_err();
+        assert_eq!(missing.0, ["Boss", "Outro"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown subplan 'Mystery'")]
+    fn calling_unknown_template_panics() {
+        let library = PlanLibrary::<()>::default();
+        library
+            .call("Mystery")
+            .activate(Entity::new(0), &mut Commands::default(), &mut ());
+    }
+}
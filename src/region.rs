@@ -0,0 +1,466 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{LevelContext, LevelPlanElement, OnceWhen, StepInfo};
+
+/// An area of the x/y plane.
+#[derive(Clone, Copy, Debug)]
+pub enum Region {
+    Rect(Rect<f32>),
+    Circle { center: Vec2, radius: f32 },
+}
+impl Region {
+    /// A rect whose `left` exceeds its `right` or whose `bottom` exceeds its
+    /// `top` covers the same area as its normalised counterpart.
+    pub fn contains(&self, point: Vec3) -> bool {
+        match self {
+            Region::Rect(rect) => {
+                let (left, right) = ordered(rect.left, rect.right);
+                let (bottom, top) = ordered(rect.bottom, rect.top);
+                point.x() >= left && point.x() <= right && point.y() >= bottom && point.y() <= top
+            }
+            Region::Circle { center, radius } => {
+                (Vec2::new(point.x(), point.y()) - *center).length_squared() <= radius * radius
+            }
+        }
+    }
+
+    pub fn random_point(&self, rng: &mut impl Rng) -> Vec3 {
+        match self {
+            Region::Rect(rect) => Vec3::new(
+                between(rng, rect.left, rect.right),
+                between(rng, rect.bottom, rect.top),
+                0.0,
+            ),
+            Region::Circle { center, radius } => {
+                let angle = rng.gen_range(0.0, std::f32::consts::PI * 2.0);
+                let distance = radius * rng.gen::<f32>().sqrt();
+                Vec3::new(
+                    center.x() + angle.cos() * distance,
+                    center.y() + angle.sin() * distance,
+                    0.0,
+                )
+            }
+        }
+    }
+}
+
+/// A random value between `a` and `b` in either order, or the value itself
+/// when they are equal, which `gen_range` would panic on.
+fn between(rng: &mut impl Rng, a: f32, b: f32) -> f32 {
+    let (low, high) = ordered(a, b);
+    if low < high {
+        rng.gen_range(low, high)
+    } else {
+        low
+    }
+}
+
+fn ordered(a: f32, b: f32) -> (f32, f32) {
+    (a.min(b), a.max(b))
+}
+
+/// Number of region elements currently watching a position, across all
+/// plans. `TrackedTransforms` skips its queries while there are none.
+static ACTIVE_REGIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts its element in `ACTIVE_REGIONS` between `enter` and `leave`, or
+/// until dropped.
+#[derive(Default)]
+struct RegionActivity {
+    counted: bool,
+}
+impl RegionActivity {
+    fn enter(&mut self) {
+        if !self.counted {
+            ACTIVE_REGIONS.fetch_add(1, Ordering::Relaxed);
+            self.counted = true;
+        }
+    }
+
+    fn leave(&mut self) {
+        if self.counted {
+            ACTIVE_REGIONS.fetch_sub(1, Ordering::Relaxed);
+            self.counted = false;
+        }
+    }
+}
+impl Drop for RegionActivity {
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
+
+/// The translations of entities, captured from the world when a
+/// `LevelContext` is built and returned from its `tracked_transforms` for
+/// `Tracker::entity` and `Tracker::marker`.
+#[derive(Clone, Debug, Default)]
+pub struct TrackedTransforms {
+    entities: HashMap<Entity, Vec3>,
+    markers: HashMap<TypeId, Vec3>,
+}
+impl TrackedTransforms {
+    /// Whether any `EnterRegion` or `InRegion` is active. Until one is,
+    /// `capture` and `with_marker` capture nothing.
+    pub fn needed() -> bool {
+        ACTIVE_REGIONS.load(Ordering::Relaxed) > 0
+    }
+
+    /// Captures the translation of every entity with a `Transform`.
+    pub fn capture(world: &World) -> Self {
+        if !Self::needed() {
+            return Self::default();
+        }
+        Self {
+            entities: world
+                .query::<(Entity, &Transform)>()
+                .iter()
+                .map(|(entity, transform)| (entity, transform.translation()))
+                .collect(),
+            markers: HashMap::new(),
+        }
+    }
+
+    /// Also captures the translation of the first entity with a `C`
+    /// component, such as the player.
+    pub fn with_marker<C: Send + Sync + 'static>(mut self, world: &World) -> Self {
+        if !Self::needed() {
+            return self;
+        }
+        if let Some((_, transform)) = world.query::<(&C, &Transform)>().iter().next() {
+            self.markers
+                .insert(TypeId::of::<C>(), transform.translation());
+        }
+        self
+    }
+
+    pub fn entity(&self, entity: Entity) -> Option<Vec3> {
+        self.entities.get(&entity).copied()
+    }
+
+    pub fn marker<C: 'static>(&self) -> Option<Vec3> {
+        self.markers.get(&TypeId::of::<C>()).copied()
+    }
+}
+
+type Position<T> = Box<dyn Fn(&T) -> Option<Vec3> + Send + Sync + 'static>;
+
+/// Fetches the position a region element watches. Any closure returning
+/// `None` when there is nothing to track converts into a tracker.
+pub struct Tracker<T> {
+    position: Position<T>,
+}
+impl<T> Tracker<T> {
+    pub fn new(position: impl Fn(&T) -> Option<Vec3> + Send + Sync + 'static) -> Self {
+        Self {
+            position: Box::new(position),
+        }
+    }
+
+    pub fn position(&self, context: &T) -> Option<Vec3> {
+        (self.position)(context)
+    }
+
+    fn inside(&self, region: &Region, context: &T) -> bool {
+        self.position(context)
+            .is_some_and(|position| region.contains(position))
+    }
+}
+impl<T: LevelContext> Tracker<T> {
+    /// Tracks the `Transform` of `entity`.
+    pub fn entity(entity: Entity) -> Self {
+        Self::new(move |context: &T| context.tracked_transforms()?.entity(entity))
+    }
+
+    /// Tracks the `Transform` of the entity captured with
+    /// `TrackedTransforms::with_marker::<C>`.
+    pub fn marker<C: 'static>() -> Self {
+        Self::new(|context: &T| context.tracked_transforms()?.marker::<C>())
+    }
+}
+impl<T, F: Fn(&T) -> Option<Vec3> + Send + Sync + 'static> From<F> for Tracker<T> {
+    fn from(position: F) -> Self {
+        Self::new(position)
+    }
+}
+
+/// Waits until the tracked position is inside the region, then runs
+/// `element` to completion. A tracker returning `None` counts as outside.
+pub struct EnterRegion<T> {
+    once: OnceWhen<T>,
+    activity: RegionActivity,
+}
+impl<T: 'static> EnterRegion<T> {
+    pub fn new(
+        region: Region,
+        tracker: impl Into<Tracker<T>>,
+        element: impl LevelPlanElement<T> + 'static,
+    ) -> Self {
+        let tracker = tracker.into();
+        Self {
+            once: OnceWhen::new(move |context: &T| tracker.inside(&region, context), element),
+            activity: RegionActivity::default(),
+        }
+    }
+}
+impl<T> LevelPlanElement<T> for EnterRegion<T> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        self.once.step(level, commands, context, info)
+    }
+
+    fn activate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.activity.enter();
+        self.once.activate(level, commands, context);
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.activity.leave();
+        self.once.deactivate(level, commands, context);
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.once.suspend(level, commands, context);
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.once.resume(level, commands, context);
+    }
+
+    fn reset(&mut self) {
+        self.activity.leave();
+        self.once.reset();
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        self.once.jump_to(label, level, commands, context)
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        self.once.children()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        self.once.children_mut()
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        self.once.active_children()
+    }
+}
+
+struct Child<T> {
+    element: Box<dyn LevelPlanElement<T>>,
+    active: bool,
+}
+impl<T> Child<T> {
+    fn new(element: impl LevelPlanElement<T> + 'static) -> Self {
+        Self {
+            element: Box::new(element),
+            active: false,
+        }
+    }
+
+    fn start(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.stop(level, commands, context);
+        self.element.activate(level, commands, context);
+        self.active = true;
+    }
+
+    fn stop(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if self.active {
+            self.element.deactivate(level, commands, context);
+            self.active = false;
+        }
+    }
+
+    fn step(&mut self, level: Entity, commands: &mut Commands, context: &mut T, info: &StepInfo) {
+        if self.active && !self.element.step(level, commands, context, info) {
+            self.stop(level, commands, context);
+        }
+    }
+}
+
+/// Watches the tracked position and runs `on_enter` each time it enters the
+/// region, `inside` for as long as it stays there and `on_exit` each time it
+/// leaves. Never completes on its own.
+pub struct InRegion<T> {
+    region: Region,
+    tracker: Tracker<T>,
+    on_enter: Option<Child<T>>,
+    inside: Option<Child<T>>,
+    on_exit: Option<Child<T>>,
+    was_inside: bool,
+    activity: RegionActivity,
+}
+impl<T> InRegion<T> {
+    pub fn new(region: Region, tracker: impl Into<Tracker<T>>) -> Self {
+        Self {
+            region,
+            tracker: tracker.into(),
+            on_enter: None,
+            inside: None,
+            on_exit: None,
+            was_inside: false,
+            activity: RegionActivity::default(),
+        }
+    }
+
+    pub fn on_enter(mut self, element: impl LevelPlanElement<T> + 'static) -> Self {
+        self.on_enter = Some(Child::new(element));
+        self
+    }
+
+    pub fn inside(mut self, element: impl LevelPlanElement<T> + 'static) -> Self {
+        self.inside = Some(Child::new(element));
+        self
+    }
+
+    pub fn on_exit(mut self, element: impl LevelPlanElement<T> + 'static) -> Self {
+        self.on_exit = Some(Child::new(element));
+        self
+    }
+
+    fn handlers(&mut self) -> impl Iterator<Item = &mut Child<T>> {
+        self.on_enter
+            .iter_mut()
+            .chain(self.inside.iter_mut())
+            .chain(self.on_exit.iter_mut())
+    }
+}
+impl<T> LevelPlanElement<T> for InRegion<T> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        let is_inside = self.tracker.inside(&self.region, context);
+        if is_inside && !self.was_inside {
+            for child in self.on_enter.iter_mut().chain(self.inside.iter_mut()) {
+                child.start(level, commands, context);
+            }
+        } else if !is_inside && self.was_inside {
+            if let Some(child) = self.inside.as_mut() {
+                child.stop(level, commands, context);
+            }
+            if let Some(child) = self.on_exit.as_mut() {
+                child.start(level, commands, context);
+            }
+        }
+        self.was_inside = is_inside;
+        for child in self.handlers() {
+            child.step(level, commands, context, info);
+        }
+        true
+    }
+
+    fn activate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {
+        self.was_inside = false;
+        self.activity.enter();
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.activity.leave();
+        for child in self.handlers() {
+            child.stop(level, commands, context);
+        }
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        for child in self.handlers().filter(|child| child.active) {
+            child.element.suspend(level, commands, context);
+        }
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        for child in self.handlers().filter(|child| child.active) {
+            child.element.resume(level, commands, context);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.was_inside = false;
+        self.activity.leave();
+        for child in self.handlers() {
+            child.active = false;
+            child.element.reset();
+        }
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        self.handlers()
+            .filter(|child| child.active)
+            .any(|child| child.element.jump_to(label, level, commands, context))
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        self.on_enter
+            .iter()
+            .chain(self.inside.iter())
+            .chain(self.on_exit.iter())
+            .map(|child| child.element.as_ref())
+            .collect()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        self.handlers().map(|child| &mut child.element).collect()
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        self.on_enter
+            .iter()
+            .chain(self.inside.iter())
+            .chain(self.on_exit.iter())
+            .enumerate()
+            .filter(|(_, child)| child.active)
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn inverted_rects_cover_their_normalised_area() {
+        let region = Region::Rect(Rect {
+            left: 10.0,
+            right: -10.0,
+            top: -5.0,
+            bottom: 5.0,
+        });
+        assert!(region.contains(Vec3::new(0.0, 0.0, 0.0)));
+        assert!(!region.contains(Vec3::new(0.0, 6.0, 0.0)));
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            assert!(region.contains(region.random_point(&mut rng)));
+        }
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    frame_steps, level_plan_system, run_level_plans, Blackboard, LevelContext, LevelPlan,
+    PlanEvent, PlanEventKind, PlanTracer,
+};
+
+/// One element transition of a recorded or replayed plan.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transition {
+    /// The id of the entity holding the plan in the recorded session.
+    #[serde(default)]
+    pub plan: u32,
+    /// The path in the plan, prefixed with the layer name for layers of a
+    /// `LevelPlans`.
+    pub path: String,
+    pub element: String,
+    pub kind: PlanEventKind,
+}
+impl From<PlanEvent> for Transition {
+    fn from(event: PlanEvent) -> Self {
+        Self {
+            plan: event.plan.id(),
+            path: event.path,
+            element: event.element,
+            kind: event.kind,
+        }
+    }
+}
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {:?}", self.path, self.element, self.kind)
+    }
+}
+
+/// One step of a recording.
+///
+/// `Blackboard`s are not serializable and are not recorded. A context that
+/// carries one has to mark it `#[serde(skip)]`, and a replayed plan starts
+/// from an empty blackboard, so it only reproduces the recording when the
+/// values it reads are ones the plan wrote itself.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedFrame<T> {
+    /// The context as built at the start of the frame, before any plan ran.
+    pub context: T,
+    /// The time plans were advanced by in the frame.
+    #[serde(default)]
+    pub delta: Duration,
+    pub transitions: Vec<Transition>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlanRecording<T> {
+    pub frames: Vec<RecordedFrame<T>>,
+}
+impl<T> Default for PlanRecording<T> {
+    fn default() -> Self {
+        Self { frames: Vec::new() }
+    }
+}
+impl<T> PlanRecording<T> {
+    /// The ids of every plan with recorded transitions, in the order they
+    /// first appear.
+    pub fn plans(&self) -> Vec<u32> {
+        let mut plans = Vec::new();
+        for transition in self.frames.iter().flat_map(|frame| &frame.transitions) {
+            if !plans.contains(&transition.plan) {
+                plans.push(transition.plan);
+            }
+        }
+        plans
+    }
+}
+impl<T: Serialize> PlanRecording<T> {
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+}
+impl<T: DeserializeOwned> PlanRecording<T> {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+/// Insert as a resource and run `recording_level_plan_system` in place of
+/// `level_plan_system` to record every frame of a play session.
+pub struct PlanRecorder<T> {
+    recording: PlanRecording<T>,
+    tracer: PlanTracer,
+}
+impl<T> Default for PlanRecorder<T> {
+    fn default() -> Self {
+        Self {
+            recording: PlanRecording::default(),
+            tracer: PlanTracer::default(),
+        }
+    }
+}
+impl<T> PlanRecorder<T> {
+    pub fn recording(&self) -> &PlanRecording<T> {
+        &self.recording
+    }
+
+    pub fn take(&mut self) -> PlanRecording<T> {
+        std::mem::take(&mut self.recording)
+    }
+}
+impl<T: Serialize> PlanRecorder<T> {
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.recording.save(path)
+    }
+}
+
+/// Behaves like `level_plan_system`, additionally appending the context and
+/// the transitions of every plan to the `PlanRecorder<T>` resource for each
+/// step, so with a `PlanTimestep<T>` a frame may record several or none.
+/// Transitions are recorded through the recorder's own tracer, so a
+/// `PlanTracer` resource is ignored while recording.
+pub fn recording_level_plan_system<T>(world: &mut World, resources: &mut Resources)
+where
+    T: LevelContext + Clone + Send + Sync + 'static,
+{
+    let recorder_tracer = resources
+        .get::<PlanRecorder<T>>()
+        .map(|recorder| recorder.tracer.clone());
+    let tracer = match recorder_tracer {
+        Some(tracer) => tracer,
+        None => {
+            level_plan_system::<T>(world, resources);
+            return;
+        }
+    };
+    for delta in frame_steps::<T>(resources) {
+        let mut context = T::build(world, resources);
+        let snapshot = context.clone();
+        run_level_plans(world, resources, &mut context, Some(&tracer), delta);
+        if let Some(mut recorder) = resources.get_mut::<PlanRecorder<T>>() {
+            recorder.recording.frames.push(RecordedFrame {
+                context: snapshot,
+                delta,
+                transitions: tracer.drain().into_iter().map(Transition::from).collect(),
+            });
+        }
+    }
+}
+
+/// The first frame at which a replay produced different transitions from the
+/// recording.
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    pub frame: usize,
+    pub expected: Vec<Transition>,
+    pub actual: Vec<Transition>,
+}
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let index = self
+            .expected
+            .iter()
+            .zip(&self.actual)
+            .take_while(|(expected, actual)| expected == actual)
+            .count();
+        let describe = |transition: Option<&Transition>| match transition {
+            Some(transition) => transition.to_string(),
+            None => "nothing".to_string(),
+        };
+        write!(
+            f,
+            "frame {}, transition {}: expected {}, got {}",
+            self.frame,
+            index,
+            describe(self.expected.get(index)),
+            describe(self.actual.get(index))
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The recording holds the transitions of the plans with these ids,
+    /// which have to be replayed one at a time with `replay_plan`.
+    MultiplePlans(Vec<u32>),
+}
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::MultiplePlans(plans) => {
+                let plans: Vec<String> = plans.iter().map(|plan| plan.to_string()).collect();
+                write!(
+                    f,
+                    "recording holds transitions of plans {}, pick one with replay_plan",
+                    plans.join(", ")
+                )
+            }
+        }
+    }
+}
+impl std::error::Error for ReplayError {}
+
+/// Replays a recording of a single plan with `replay_plan`, failing if the
+/// recording holds the transitions of more than one plan.
+pub fn replay<T: LevelContext + Clone + 'static>(
+    recording: &PlanRecording<T>,
+    plan: LevelPlan<T>,
+) -> Result<Option<Divergence>, ReplayError> {
+    let plans = recording.plans();
+    if plans.len() > 1 {
+        return Err(ReplayError::MultiplePlans(plans));
+    }
+    Ok(replay_plan(
+        recording,
+        plans.first().copied().unwrap_or(0),
+        plan,
+    ))
+}
+
+/// Runs `plan` headlessly against the recorded contexts, one frame per
+/// recorded frame, and returns the first frame whose transitions differ from
+/// the ones recorded for the plan with id `recorded`. Transitions of other
+/// plans and of `LevelPlans` layers, which are not replayed, are ignored.
+///
+/// The plan runs in an empty world on an entity with an empty `Blackboard`,
+/// so it must only depend on its context and `StepInfo`, whose clock advances
+/// by the recorded time of every frame.
+pub fn replay_plan<T: LevelContext + Clone + 'static>(
+    recording: &PlanRecording<T>,
+    recorded: u32,
+    plan: LevelPlan<T>,
+) -> Option<Divergence> {
+    let mut world = World::new();
+    let mut resources = Resources::default();
+    let tracer = PlanTracer::default();
+    world.spawn((plan, Blackboard::default()));
+    for (frame, recorded_frame) in recording.frames.iter().enumerate() {
+        let mut context = recorded_frame.context.clone();
+        run_level_plans(
+            &mut world,
+            &mut resources,
+            &mut context,
+            Some(&tracer),
+            recorded_frame.delta,
+        );
+        let actual: Vec<Transition> = tracer
+            .drain()
+            .into_iter()
+            .map(|event| Transition {
+                plan: recorded,
+                ..Transition::from(event)
+            })
+            .collect();
+        let expected: Vec<Transition> = recorded_frame
+            .transitions
+            .iter()
+            .filter(|transition| transition.plan == recorded && transition.path.starts_with('/'))
+            .cloned()
+            .collect();
+        if actual != expected {
+            return Some(Divergence {
+                frame,
+                expected,
+                actual,
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Nop, OnceWhen, Sequence};
+
+    #[derive(Clone, Default, Serialize, Deserialize)]
+    struct Progress(u32);
+    impl LevelContext for Progress {
+        fn build(_world: &World, resources: &Resources) -> Self {
+            Progress(*resources.get::<u32>().unwrap())
+        }
+    }
+
+    fn plan(threshold: u32) -> LevelPlan<Progress> {
+        LevelPlan::new(
+            Sequence::default()
+                .push(OnceWhen::new(
+                    move |progress: &Progress| progress.0 >= threshold,
+                    Nop,
+                ))
+                .push(Nop),
+        )
+    }
+
+    fn record(plans: usize) -> PlanRecording<Progress> {
+        let mut world = World::new();
+        let mut resources = Resources::default();
+        resources.insert(PlanRecorder::<Progress>::default());
+        for _ in 0..plans {
+            world.spawn((plan(2), Blackboard::default()));
+        }
+        for progress in 0..5u32 {
+            resources.insert(progress);
+            recording_level_plan_system::<Progress>(&mut world, &mut resources);
+        }
+        let recording = resources
+            .get_mut::<PlanRecorder<Progress>>()
+            .unwrap()
+            .take();
+        recording
+    }
+
+    #[test]
+    fn replaying_the_recorded_plan_matches() {
+        let recording = record(1);
+        assert_eq!(recording.frames.len(), 5);
+        assert!(replay(&recording, plan(2)).unwrap().is_none());
+    }
+
+    #[test]
+    fn replaying_a_changed_plan_diverges() {
+        let recording = record(1);
+        let divergence = replay(&recording, plan(3)).unwrap().unwrap();
+        assert_eq!(divergence.frame, 2);
+        assert!(divergence.actual.len() < divergence.expected.len());
+    }
+
+    #[test]
+    fn several_plans_are_replayed_one_at_a_time() {
+        let recording = record(2);
+        let plans = recording.plans();
+        assert!(matches!(
+            replay(&recording, plan(2)),
+            Err(ReplayError::MultiplePlans(ref error)) if *error == plans
+        ));
+        for id in plans {
+            assert!(replay_plan(&recording, id, plan(2)).is_none());
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use bevy::prelude::*;
+
+use crate::{LevelPlanElement, StepInfo};
+
+#[derive(Default)]
+struct SignalState {
+    raised: bool,
+    count: u64,
+}
+
+#[derive(Default)]
+struct BarrierState {
+    generation: u64,
+    /// The tokens of the `JoinBarrier`s waiting in this generation.
+    arrived: HashSet<u64>,
+}
+
+#[derive(Default)]
+struct Shared {
+    signals: HashMap<String, SignalState>,
+    barriers: HashMap<String, BarrierState>,
+    next_token: u64,
+}
+
+/// Named signals and barriers shared between plans. Insert it as a resource
+/// next to `level_plan_system` and hand clones to the `Raise`, `AwaitSignal`
+/// and `JoinBarrier` elements of every plan which should coordinate; other
+/// systems can raise and lower signals through the resource.
+///
+/// Clones share the same signals.
+#[derive(Clone, Default)]
+pub struct PlanSignals {
+    shared: Arc<Mutex<Shared>>,
+}
+impl PlanSignals {
+    /// Raises the signal, which stays raised until lowered.
+    pub fn raise(&self, name: &str) {
+        let mut shared = self.shared.lock().unwrap();
+        let signal = shared.signals.entry(name.to_string()).or_default();
+        signal.raised = true;
+        signal.count += 1;
+    }
+
+    pub fn lower(&self, name: &str) {
+        if let Some(signal) = self.shared.lock().unwrap().signals.get_mut(name) {
+            signal.raised = false;
+        }
+    }
+
+    pub fn is_raised(&self, name: &str) -> bool {
+        self.shared
+            .lock()
+            .unwrap()
+            .signals
+            .get(name)
+            .is_some_and(|signal| signal.raised)
+    }
+
+    /// How many times the signal has been raised.
+    pub fn raise_count(&self, name: &str) -> u64 {
+        self.shared
+            .lock()
+            .unwrap()
+            .signals
+            .get(name)
+            .map_or(0, |signal| signal.count)
+    }
+
+    /// A token telling apart the `JoinBarrier`s arriving at barriers, even
+    /// ones in layers of the same plan entity.
+    fn token(&self) -> u64 {
+        let mut shared = self.shared.lock().unwrap();
+        shared.next_token += 1;
+        shared.next_token
+    }
+
+    /// Registers the `JoinBarrier` with `token` at the barrier and returns
+    /// the generation it is waiting on. The barrier opens, moving to the next
+    /// generation, once `parties` distinct ones have arrived.
+    fn arrive(&self, name: &str, parties: usize, token: u64) -> u64 {
+        let mut shared = self.shared.lock().unwrap();
+        let barrier = shared.barriers.entry(name.to_string()).or_default();
+        let generation = barrier.generation;
+        barrier.arrived.insert(token);
+        if barrier.arrived.len() >= parties {
+            barrier.arrived.clear();
+            barrier.generation += 1;
+        }
+        generation
+    }
+
+    fn leave(&self, name: &str, generation: u64, token: u64) {
+        if let Some(barrier) = self.shared.lock().unwrap().barriers.get_mut(name) {
+            if barrier.generation == generation {
+                barrier.arrived.remove(&token);
+            }
+        }
+    }
+
+    fn barrier_generation(&self, name: &str) -> u64 {
+        self.shared
+            .lock()
+            .unwrap()
+            .barriers
+            .get(name)
+            .map_or(0, |barrier| barrier.generation)
+    }
+}
+
+/// Raises a signal when activated and completes immediately.
+pub struct Raise {
+    signals: PlanSignals,
+    name: String,
+}
+impl Raise {
+    pub fn new(signals: &PlanSignals, name: impl Into<String>) -> Self {
+        Self {
+            signals: signals.clone(),
+            name: name.into(),
+        }
+    }
+}
+impl<T> LevelPlanElement<T> for Raise {
+    fn step(
+        &mut self,
+        _level: Entity,
+        _commands: &mut Commands,
+        _context: &mut T,
+        _info: &StepInfo,
+    ) -> bool {
+        false
+    }
+
+    fn activate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {
+        self.signals.raise(&self.name);
+    }
+
+    fn label(&self) -> String {
+        format!("Raise({})", self.name)
+    }
+}
+
+/// Waits until a signal is raised. By default a signal raised before
+/// activation which is still raised counts; with `next` only a raise after
+/// activation does.
+pub struct AwaitSignal {
+    signals: PlanSignals,
+    name: String,
+    next: bool,
+    baseline: u64,
+}
+impl AwaitSignal {
+    pub fn new(signals: &PlanSignals, name: impl Into<String>) -> Self {
+        Self {
+            signals: signals.clone(),
+            name: name.into(),
+            next: false,
+            baseline: 0,
+        }
+    }
+
+    pub fn next(mut self) -> Self {
+        self.next = true;
+        self
+    }
+}
+impl<T> LevelPlanElement<T> for AwaitSignal {
+    fn step(
+        &mut self,
+        _level: Entity,
+        _commands: &mut Commands,
+        _context: &mut T,
+        _info: &StepInfo,
+    ) -> bool {
+        if self.next {
+            self.signals.raise_count(&self.name) <= self.baseline
+        } else {
+            !self.signals.is_raised(&self.name)
+        }
+    }
+
+    fn activate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {
+        self.baseline = self.signals.raise_count(&self.name);
+    }
+
+    fn reset(&mut self) {
+        self.baseline = 0;
+    }
+
+    fn label(&self) -> String {
+        format!("AwaitSignal({})", self.name)
+    }
+}
+
+/// Waits until `parties` `JoinBarrier`s, usually in different plans or
+/// layers, have reached the same named barrier, then completes in all of
+/// them. The barrier can be reused afterwards.
+pub struct JoinBarrier {
+    signals: PlanSignals,
+    name: String,
+    parties: usize,
+    token: u64,
+    waiting: Option<u64>,
+}
+impl JoinBarrier {
+    pub fn new(signals: &PlanSignals, name: impl Into<String>, parties: usize) -> Self {
+        Self {
+            signals: signals.clone(),
+            name: name.into(),
+            parties,
+            token: signals.token(),
+            waiting: None,
+        }
+    }
+}
+impl<T> LevelPlanElement<T> for JoinBarrier {
+    fn step(
+        &mut self,
+        _level: Entity,
+        _commands: &mut Commands,
+        _context: &mut T,
+        _info: &StepInfo,
+    ) -> bool {
+        self.waiting
+            .is_some_and(|generation| self.signals.barrier_generation(&self.name) <= generation)
+    }
+
+    fn activate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {
+        self.waiting = Some(self.signals.arrive(&self.name, self.parties, self.token));
+    }
+
+    fn deactivate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {
+        if let Some(generation) = self.waiting.take() {
+            self.signals.leave(&self.name, generation, self.token);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.waiting = None;
+    }
+
+    fn label(&self) -> String {
+        format!("JoinBarrier({}, {})", self.name, self.parties)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{run, Context, Log},
+        LevelPlan, LevelPlans, Sequence,
+    };
+
+    fn plan(signals: &PlanSignals, log: &Log, name: &str, steps: usize) -> LevelPlan<Context> {
+        LevelPlan::new(
+            Sequence::default()
+                .push(log.probe(name, steps))
+                .push(JoinBarrier::new(signals, "meet", 2))
+                .push(log.probe(&format!("{} joined", name), 100)),
+        )
+    }
+
+    fn joined(log: &Log) -> Vec<String> {
+        log.take()
+            .into_iter()
+            .filter(|entry| entry.ends_with("joined activate"))
+            .collect()
+    }
+
+    #[test]
+    fn barrier_waits_for_both_plans() {
+        let signals = PlanSignals::default();
+        let log = Log::default();
+        let mut world = World::new();
+        world.spawn((plan(&signals, &log, "fast", 1),));
+        world.spawn((plan(&signals, &log, "slow", 4),));
+        for _ in 0..4 {
+            run(&mut world);
+        }
+        assert!(joined(&log).is_empty());
+        run(&mut world);
+        run(&mut world);
+        let mut both = joined(&log);
+        both.sort();
+        assert_eq!(both, ["fast joined activate", "slow joined activate"]);
+    }
+
+    #[test]
+    fn barrier_counts_layers_of_one_entity_separately() {
+        let signals = PlanSignals::default();
+        let log = Log::default();
+        let mut world = World::new();
+        world.spawn((LevelPlans::default()
+            .with("fast", 10, plan(&signals, &log, "fast", 1))
+            .with("slow", 0, plan(&signals, &log, "slow", 4)),));
+        for _ in 0..4 {
+            run(&mut world);
+        }
+        assert!(joined(&log).is_empty());
+        run(&mut world);
+        run(&mut world);
+        assert_eq!(joined(&log).len(), 2);
+    }
+}
@@ -0,0 +1,217 @@
+use bevy::prelude::*;
+
+use crate::{LevelPlan, LevelPlanElement, PendingJump};
+
+fn parse_path(path: &str) -> Option<Vec<usize>> {
+    path.split('/')
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().ok())
+        .collect()
+}
+
+fn path_exists<T>(element: &dyn LevelPlanElement<T>, path: &[usize]) -> bool {
+    match path.split_first() {
+        Some((&index, rest)) => element
+            .children()
+            .get(index)
+            .is_some_and(|child| path_exists(*child, rest)),
+        None => true,
+    }
+}
+
+/// The path from `element` to the child a label refers to.
+pub(crate) fn find_label<T>(element: &dyn LevelPlanElement<T>, label: &str) -> Option<Vec<usize>> {
+    if let Some(index) = element.label_index(label) {
+        return Some(vec![index]);
+    }
+    element
+        .children()
+        .into_iter()
+        .enumerate()
+        .find_map(|(index, child)| {
+            let mut path = find_label(child, label)?;
+            path.insert(0, index);
+            Some(path)
+        })
+}
+
+/// Jumps along the active branch if possible, otherwise skips to wherever in
+/// the plan the label is. Returns `false` if neither worked.
+pub(crate) fn jump_to_label<T>(
+    element: &mut Box<dyn LevelPlanElement<T>>,
+    label: &str,
+    level: Entity,
+    commands: &mut Commands,
+    context: &mut T,
+) -> bool {
+    if element.jump_to(label, level, commands, context) {
+        return true;
+    }
+    match find_label(element.as_ref(), label) {
+        Some(path) => skip_to(element, &path, false, level, commands, context),
+        None => false,
+    }
+}
+
+/// Skips the innermost running child along the active branch which its
+/// parent knows how to skip.
+pub(crate) fn skip_current<T>(
+    element: &mut Box<dyn LevelPlanElement<T>>,
+    level: Entity,
+    commands: &mut Commands,
+    context: &mut T,
+) -> bool {
+    if let Some(&index) = element.active_children().first() {
+        if let Some(child) = element.children_mut().into_iter().nth(index) {
+            if skip_current(child, level, commands, context) {
+                return true;
+            }
+        }
+    }
+    element.skip_current(level, commands, context)
+}
+
+pub(crate) fn skip_to<T>(
+    element: &mut Box<dyn LevelPlanElement<T>>,
+    path: &[usize],
+    fast_forward: bool,
+    level: Entity,
+    commands: &mut Commands,
+    context: &mut T,
+) -> bool {
+    match path.split_first() {
+        Some((&index, rest)) => {
+            if !element.active_children().contains(&index) {
+                // Prepare the child so activating it starts where the rest of
+                // the path leads rather than at its first element.
+                let entered = match (element.children_mut().into_iter().nth(index), rest.first()) {
+                    (Some(child), Some(&next)) if !fast_forward => child.enter_at(Some(next)),
+                    _ => false,
+                };
+                if !element.skip_to_child(index, fast_forward, level, commands, context) {
+                    if let (true, Some(child)) =
+                        (entered, element.children_mut().into_iter().nth(index))
+                    {
+                        child.enter_at(None);
+                    }
+                    return false;
+                }
+            }
+            match element.children_mut().into_iter().nth(index) {
+                Some(child) => skip_to(child, rest, fast_forward, level, commands, context),
+                None => rest.is_empty(),
+            }
+        }
+        None => true,
+    }
+}
+
+impl<T> LevelPlan<T> {
+    /// Finishes the innermost running step of a `Sequence` (or other element
+    /// which supports skipping) on the next run of `level_plan_system`.
+    pub fn skip_current(&mut self) {
+        self.pending_jump = Some(PendingJump::SkipCurrent);
+    }
+
+    /// Moves execution to the element at `path`, written as child indices
+    /// from the root like the paths in `PlanEvent`, e.g. `/3` or `/1/0`, on
+    /// the next run of `level_plan_system`. Every element along the way must
+    /// either already be running the next child or be able to skip to it.
+    /// Returns `false` without doing anything if no element has that path.
+    pub fn skip_to(&mut self, path: &str) -> bool {
+        match parse_path(path) {
+            Some(path) if path_exists(self.plan.as_ref(), &path) => {
+                self.pending_jump = Some(PendingJump::SkipTo(path));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// When enabled, `skip_to` activates and deactivates every element it
+    /// passes over, without stepping them, so their side effects on
+    /// components and resources are still applied.
+    pub fn fast_forward(&mut self, enabled: bool) {
+        self.fast_forward = enabled;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{run, Context, Log},
+        Sequence,
+    };
+
+    fn spawn(world: &mut World, log: &Log) -> Entity {
+        world.spawn((LevelPlan::<Context>::new(
+            Sequence::default()
+                .push(log.probe("intro", 100))
+                .push(
+                    Sequence::default()
+                        .push(log.probe("first", 100))
+                        .label("second")
+                        .push(log.probe("second", 100)),
+                )
+                .push(log.probe("outro", 100)),
+        ),))
+    }
+
+    fn plan(world: &mut World, entity: Entity) -> Mut<'_, LevelPlan<Context>> {
+        world.get_mut::<LevelPlan<Context>>(entity).unwrap()
+    }
+
+    fn started(world: &mut World, log: &Log) -> Entity {
+        let entity = spawn(world, log);
+        run(world);
+        log.take();
+        entity
+    }
+
+    #[test]
+    fn skip_to_enters_a_nested_sequence_part_way() {
+        let log = Log::default();
+        let mut world = World::new();
+        let entity = started(&mut world, &log);
+        assert!(plan(&mut world, entity).skip_to("/1/1"));
+        assert!(!plan(&mut world, entity).skip_to("/1/2"));
+        run(&mut world);
+        assert_eq!(
+            log.take(),
+            ["intro deactivate", "second activate", "second step"]
+        );
+    }
+
+    #[test]
+    fn jump_to_finds_labels_of_nested_sequences() {
+        let log = Log::default();
+        let mut world = World::new();
+        let entity = started(&mut world, &log);
+        assert!(plan(&mut world, entity).jump_to("second"));
+        run(&mut world);
+        assert_eq!(
+            log.take(),
+            ["intro deactivate", "second activate", "second step"]
+        );
+    }
+
+    #[test]
+    fn skip_current_skips_the_innermost_step() {
+        let log = Log::default();
+        let mut world = World::new();
+        let entity = started(&mut world, &log);
+        plan(&mut world, entity).skip_current();
+        run(&mut world);
+        assert_eq!(
+            log.take(),
+            ["intro deactivate", "first activate", "first step"]
+        );
+        plan(&mut world, entity).skip_current();
+        run(&mut world);
+        assert_eq!(
+            log.take(),
+            ["first deactivate", "second activate", "second step"]
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use std::fmt;
+
+use bevy::prelude::*;
+
+use crate::{LevelPlanElement, StepInfo};
+
+enum Trigger<T> {
+    When(Box<dyn Fn(&T) -> bool + Send + Sync + 'static>),
+    Completed,
+}
+
+struct Transition<T> {
+    from: Option<usize>,
+    to: usize,
+    trigger: Trigger<T>,
+    armed: bool,
+}
+impl<T> Transition<T> {
+    fn is_self_loop(&self) -> bool {
+        self.from == Some(self.to)
+    }
+}
+
+/// Collects the states and transitions of a `StateMachine`, which `build`
+/// checks and resolves.
+pub struct StateMachineBuilder<T> {
+    initial: String,
+    states: Vec<(String, Box<dyn LevelPlanElement<T>>)>,
+    transitions: Vec<(Option<String>, String, Trigger<T>)>,
+}
+impl<T> StateMachineBuilder<T> {
+    pub fn state(
+        mut self,
+        name: impl Into<String>,
+        element: impl LevelPlanElement<T> + 'static,
+    ) -> Self {
+        self.states.push((name.into(), Box::new(element)));
+        self
+    }
+
+    /// A self-loop, where `from` and `to` are the same state, re-enters the
+    /// state once each time `guard` becomes true rather than on every step
+    /// it holds.
+    pub fn transition(
+        mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+        guard: impl Fn(&T) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.transitions
+            .push((Some(from.into()), to.into(), Trigger::When(Box::new(guard))));
+        self
+    }
+
+    /// Never fires while `to` is the current state.
+    pub fn transition_from_any(
+        mut self,
+        to: impl Into<String>,
+        guard: impl Fn(&T) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.transitions
+            .push((None, to.into(), Trigger::When(Box::new(guard))));
+        self
+    }
+
+    pub fn on_complete(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.transitions
+            .push((Some(from.into()), to.into(), Trigger::Completed));
+        self
+    }
+
+    /// Fails with every name the initial state or a transition refers to that
+    /// no state was added for.
+    pub fn build(self) -> Result<StateMachine<T>, UnknownStates> {
+        let (names, states): (Vec<_>, Vec<_>) = self.states.into_iter().unzip();
+        let mut unknown = Vec::new();
+        let mut index_of = |name: String| {
+            let index = names.iter().position(|state| *state == name);
+            if index.is_none() {
+                unknown.push(name);
+            }
+            index.unwrap_or(0)
+        };
+        let initial = index_of(self.initial);
+        let transitions = self
+            .transitions
+            .into_iter()
+            .map(|(from, to, trigger)| Transition {
+                from: from.map(&mut index_of),
+                to: index_of(to),
+                trigger,
+                armed: true,
+            })
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            unknown.dedup();
+            return Err(UnknownStates(unknown));
+        }
+        Ok(StateMachine {
+            names,
+            states,
+            initial,
+            transitions,
+            current: None,
+        })
+    }
+}
+
+/// A set of named states, each an element, which is entered with `activate`
+/// and left with `deactivate` as transitions fire. States may themselves be
+/// `StateMachine`s.
+///
+/// Guarded transitions are checked, in the order they were added, before the
+/// current state is stepped. When the current state completes and no
+/// completion transition leaves it the whole machine completes.
+///
+/// Built with `StateMachine::builder`, which checks every state name once.
+pub struct StateMachine<T> {
+    names: Vec<String>,
+    states: Vec<Box<dyn LevelPlanElement<T>>>,
+    initial: usize,
+    transitions: Vec<Transition<T>>,
+    current: Option<usize>,
+}
+impl<T> StateMachine<T> {
+    pub fn builder(initial: impl Into<String>) -> StateMachineBuilder<T> {
+        StateMachineBuilder {
+            initial: initial.into(),
+            states: Vec::new(),
+            transitions: Vec::new(),
+        }
+    }
+
+    pub fn current_state(&self) -> Option<&str> {
+        self.current.map(|index| self.names[index].as_str())
+    }
+
+    /// Guards of self-loops leaving the current state are evaluated even
+    /// after an earlier transition was found, so they re-arm while false.
+    fn find_transition(&mut self, context: &T, completed: bool) -> Option<usize> {
+        let current = self.current?;
+        let mut found = None;
+        for transition in self.transitions.iter_mut() {
+            let leaves_current = match transition.from {
+                Some(from) => from == current,
+                None => transition.to != current,
+            };
+            if !leaves_current || found.is_some() && !transition.is_self_loop() {
+                continue;
+            }
+            let fires = match &transition.trigger {
+                Trigger::When(guard) => {
+                    let holds = guard(context);
+                    if transition.is_self_loop() {
+                        let armed = transition.armed;
+                        transition.armed = !holds;
+                        holds && armed
+                    } else {
+                        holds
+                    }
+                }
+                Trigger::Completed => completed,
+            };
+            if fires && found.is_none() {
+                found = Some(transition.to);
+            }
+        }
+        found
+    }
+
+    fn enter(&mut self, index: usize, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(current) = self.current.take() {
+            self.states[current].deactivate(level, commands, context);
+        }
+        self.states[index].activate(level, commands, context);
+        self.current = Some(index);
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownStates(pub Vec<String>);
+impl fmt::Display for UnknownStates {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown states: {}", self.0.join(", "))
+    }
+}
+impl std::error::Error for UnknownStates {}
+
+impl<T> LevelPlanElement<T> for StateMachine<T> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        let current = if let Some(current) = self.current {
+            current
+        } else {
+            return false;
+        };
+        if let Some(next) = self.find_transition(context, false) {
+            self.enter(next, level, commands, context);
+            return true;
+        }
+        if self.states[current].step(level, commands, context, info) {
+            true
+        } else if let Some(next) = self.find_transition(context, true) {
+            self.enter(next, level, commands, context);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn activate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        for transition in self.transitions.iter_mut() {
+            transition.armed = true;
+        }
+        self.enter(self.initial, level, commands, context);
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(current) = self.current.take() {
+            self.states[current].deactivate(level, commands, context);
+        }
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(current) = self.current {
+            self.states[current].suspend(level, commands, context);
+        }
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(current) = self.current {
+            self.states[current].resume(level, commands, context);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.current = None;
+        for transition in self.transitions.iter_mut() {
+            transition.armed = true;
+        }
+        for child in self.children_mut() {
+            child.reset();
+        }
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        if let Some(current) = self.current {
+            self.states[current].jump_to(label, level, commands, context)
+        } else {
+            false
+        }
+    }
+
+    fn label(&self) -> String {
+        format!("StateMachine({})", self.names[self.initial])
+    }
+
+    /// Leaves the current state as if it had completed.
+    fn skip_current(&mut self, level: Entity, commands: &mut Commands, context: &mut T) -> bool {
+        if self.current.is_none() {
+            return false;
+        }
+        match self.find_transition(context, true) {
+            Some(next) => self.enter(next, level, commands, context),
+            None => self.deactivate(level, commands, context),
+        }
+        true
+    }
+
+    fn skip_to_child(
+        &mut self,
+        index: usize,
+        _fast_forward: bool,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        if index >= self.states.len() {
+            return false;
+        }
+        self.enter(index, level, commands, context);
+        true
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        self.states.iter().map(|state| state.as_ref()).collect()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        self.states.iter_mut().collect()
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        self.current.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{activate, step, Log};
+
+    #[test]
+    fn build_reports_unknown_states() {
+        let log = Log::default();
+        let error = StateMachine::<bool>::builder("Start")
+            .state("Idle", log.probe("idle", 1))
+            .transition("Idle", "Alert", |_: &bool| true)
+            .transition_from_any("Idle", |_: &bool| true)
+            .on_complete("Alert", "Gone")
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(error.0, ["Alert", "Gone", "Start"]);
+    }
+
+    #[test]
+    fn transitions_and_completion() {
+        let log = Log::default();
+        let mut machine = StateMachine::builder("Idle")
+            .state("Idle", log.probe("idle", 100))
+            .state("Alert", log.probe("alert", 2))
+            .state("Calm", log.probe("calm", 1))
+            .transition("Idle", "Alert", |alarm: &bool| *alarm)
+            .on_complete("Alert", "Calm")
+            .build()
+            .unwrap();
+        let mut alarm = false;
+        activate(&mut machine, &mut alarm);
+        assert!(step(&mut machine, &mut alarm));
+        alarm = true;
+        assert!(step(&mut machine, &mut alarm));
+        assert_eq!(machine.current_state(), Some("Alert"));
+        assert!(step(&mut machine, &mut alarm));
+        assert!(step(&mut machine, &mut alarm));
+        assert_eq!(machine.current_state(), Some("Calm"));
+        assert!(!step(&mut machine, &mut alarm));
+        assert_eq!(
+            log.take(),
+            [
+                "idle activate",
+                "idle step",
+                "idle deactivate",
+                "alert activate",
+                "alert step",
+                "alert step",
+                "alert deactivate",
+                "calm activate",
+                "calm step",
+            ]
+        );
+    }
+
+    #[test]
+    fn self_loops_fire_once_per_rising_edge() {
+        let log = Log::default();
+        let mut machine = StateMachine::builder("Wave")
+            .state("Wave", log.probe("wave", 100))
+            .transition("Wave", "Wave", |restart: &bool| *restart)
+            .build()
+            .unwrap();
+        let mut restart = false;
+        activate(&mut machine, &mut restart);
+        restart = true;
+        step(&mut machine, &mut restart);
+        step(&mut machine, &mut restart);
+        step(&mut machine, &mut restart);
+        restart = false;
+        step(&mut machine, &mut restart);
+        restart = true;
+        step(&mut machine, &mut restart);
+        assert_eq!(
+            log.take(),
+            [
+                "wave activate",
+                "wave deactivate",
+                "wave activate",
+                "wave step",
+                "wave step",
+                "wave step",
+                "wave deactivate",
+                "wave activate",
+            ]
+        );
+    }
+}
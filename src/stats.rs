@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    instrument::{child_path, instrument, Hook},
+    LevelPlanElement, StepInfo,
+};
+
+/// Accumulated statistics of one element, as reported by `PlanStats`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NodeStats {
+    /// Child indices from the root of the plan, like the paths in `PlanEvent`.
+    pub path: String,
+    pub element: String,
+    pub activations: u64,
+    /// How often a step of the element reported it as finished.
+    pub completions: u64,
+    /// The summed `StepInfo::delta` of every step the element was running in.
+    pub active_time: Duration,
+    /// How often each child was activated, such as the if and else branches
+    /// of a `Conditional`. A zero marks a branch that was never taken.
+    pub branches: Vec<u64>,
+}
+
+struct Node {
+    stats: NodeStats,
+    children: Vec<usize>,
+}
+
+#[derive(Default)]
+struct StatsState {
+    nodes: Vec<Node>,
+    index: HashMap<(String, String), usize>,
+}
+
+/// Insert as a resource to have `level_plan_system` count, for every element
+/// of every plan, how often it was activated and completed and how long it
+/// ran, for balancing and coverage. Elements are registered as soon as their
+/// plan first runs, so ones never reached are reported with zero counts.
+///
+/// Statistics are aggregated by path and element over all plans, so running
+/// the same plan repeatedly accumulates into the same rows. Clones share the
+/// same statistics.
+#[derive(Clone, Default)]
+pub struct PlanStats {
+    state: Arc<Mutex<StatsState>>,
+}
+impl PlanStats {
+    /// The statistics of every registered element, parents before children.
+    pub fn report(&self) -> Vec<NodeStats> {
+        let state = self.state.lock().unwrap();
+        state
+            .nodes
+            .iter()
+            .map(|node| NodeStats {
+                branches: node
+                    .children
+                    .iter()
+                    .map(|&child| state.nodes[child].stats.activations)
+                    .collect(),
+                ..node.stats.clone()
+            })
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        for node in state.nodes.iter_mut() {
+            node.stats.activations = 0;
+            node.stats.completions = 0;
+            node.stats.active_time = Duration::from_secs(0);
+        }
+    }
+
+    /// One row per element with the columns `path`, `element`,
+    /// `activations`, `completions`, `active_seconds` and `branches`, the
+    /// branch counts separated by `;`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = "path,element,activations,completions,active_seconds,branches\n".to_string();
+        for node in self.report() {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                csv_field(&node.path),
+                csv_field(&node.element),
+                node.activations,
+                node.completions,
+                node.active_time.as_secs_f64(),
+                join(&node.branches, ";")
+            ));
+        }
+        csv
+    }
+
+    /// An array with one object per element, holding the same fields as the
+    /// CSV export.
+    pub fn to_json(&self) -> String {
+        let rows: Vec<String> = self
+            .report()
+            .into_iter()
+            .map(|node| {
+                format!(
+                    "  {{\"path\": {}, \"element\": {}, \"activations\": {}, \
+                     \"completions\": {}, \"active_seconds\": {}, \"branches\": [{}]}}",
+                    json_string(&node.path),
+                    json_string(&node.element),
+                    node.activations,
+                    node.completions,
+                    node.active_time.as_secs_f64(),
+                    join(&node.branches, ", ")
+                )
+            })
+            .collect();
+        format!("[\n{}\n]\n", rows.join(",\n"))
+    }
+
+    /// Starts counting for every element of a plan, registering the root
+    /// under `path` and the rest below it.
+    pub(crate) fn instrument<T: 'static>(
+        &self,
+        path: String,
+        root: &mut Box<dyn LevelPlanElement<T>>,
+    ) {
+        let hook = StatsHook {
+            stats: self.clone(),
+            index: self.register(&path, root.label()),
+            path,
+        };
+        instrument(hook, root);
+    }
+
+    fn register(&self, path: &str, element: String) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let key = (path.to_string(), element);
+        if let Some(&index) = state.index.get(&key) {
+            return index;
+        }
+        let index = state.nodes.len();
+        state.nodes.push(Node {
+            stats: NodeStats {
+                path: key.0.clone(),
+                element: key.1.clone(),
+                ..NodeStats::default()
+            },
+            children: Vec::new(),
+        });
+        state.index.insert(key, index);
+        index
+    }
+
+    fn update(&self, index: usize, update: impl FnOnce(&mut NodeStats)) {
+        update(&mut self.state.lock().unwrap().nodes[index].stats);
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn join(counts: &[u64], separator: &str) -> String {
+    counts
+        .iter()
+        .map(|count| count.to_string())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+#[derive(Clone)]
+struct StatsHook {
+    stats: PlanStats,
+    path: String,
+    index: usize,
+}
+impl<T: 'static> Hook<T> for StatsHook {
+    fn child(&self, index: usize, child: &dyn LevelPlanElement<T>) -> Self {
+        let path = child_path(&self.path, index);
+        let child = self.stats.register(&path, child.label());
+        let mut state = self.stats.state.lock().unwrap();
+        if !state.nodes[self.index].children.contains(&child) {
+            state.nodes[self.index].children.push(child);
+        }
+        Self {
+            stats: self.stats.clone(),
+            path,
+            index: child,
+        }
+    }
+
+    fn activate(&self, _element: &dyn LevelPlanElement<T>, _context: &T) {
+        self.stats
+            .update(self.index, |stats| stats.activations += 1);
+    }
+
+    fn step(
+        &self,
+        _element: &dyn LevelPlanElement<T>,
+        _context: &T,
+        info: &StepInfo,
+        running: bool,
+    ) {
+        self.stats.update(self.index, |stats| {
+            stats.active_time += info.delta;
+            if !running {
+                stats.completions += 1;
+            }
+        });
+    }
+}
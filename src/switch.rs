@@ -0,0 +1,160 @@
+use bevy::prelude::*;
+
+use crate::{LevelPlanElement, StepInfo};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwitchMode {
+    /// Leave the running arm as soon as the key stops matching it.
+    Immediate,
+    /// Keep running the arm chosen on the first step until it completes.
+    FinishCurrent,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Branch {
+    Arm(usize),
+    Otherwise,
+}
+
+/// Runs the arm matching the key extracted from the context, or the `otherwise`
+/// arm if none matches. Completes when the running arm completes, or when no
+/// arm matches and there is no `otherwise` arm.
+pub struct Switch<T, K> {
+    key: Box<dyn Fn(&T) -> K + Send + Sync + 'static>,
+    arms: Vec<(K, Box<dyn LevelPlanElement<T>>)>,
+    otherwise: Option<Box<dyn LevelPlanElement<T>>>,
+    mode: SwitchMode,
+    active: Option<Branch>,
+}
+impl<T, K: PartialEq> Switch<T, K> {
+    pub fn new(key: impl Fn(&T) -> K + Send + Sync + 'static) -> Self {
+        Self {
+            key: Box::new(key),
+            arms: Vec::new(),
+            otherwise: None,
+            mode: SwitchMode::Immediate,
+            active: None,
+        }
+    }
+
+    pub fn arm(mut self, key: K, element: impl LevelPlanElement<T> + 'static) -> Self {
+        self.arms.push((key, Box::new(element)));
+        self
+    }
+
+    pub fn otherwise(mut self, element: impl LevelPlanElement<T> + 'static) -> Self {
+        self.otherwise = Some(Box::new(element));
+        self
+    }
+
+    pub fn mode(mut self, mode: SwitchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    fn select(&self, context: &T) -> Option<Branch> {
+        let key = (self.key)(context);
+        match self.arms.iter().position(|(arm, _)| *arm == key) {
+            Some(index) => Some(Branch::Arm(index)),
+            None if self.otherwise.is_some() => Some(Branch::Otherwise),
+            None => None,
+        }
+    }
+
+    fn branch(&mut self, branch: Branch) -> &mut Box<dyn LevelPlanElement<T>> {
+        match branch {
+            Branch::Arm(index) => &mut self.arms[index].1,
+            Branch::Otherwise => self.otherwise.as_mut().unwrap(),
+        }
+    }
+}
+impl<T, K: PartialEq + Send + Sync> LevelPlanElement<T> for Switch<T, K> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        if self.active.is_none() || self.mode == SwitchMode::Immediate {
+            let selected = self.select(context);
+            if selected != self.active {
+                if let Some(active) = self.active.take() {
+                    self.branch(active).deactivate(level, commands, context);
+                }
+                if let Some(selected) = selected {
+                    self.branch(selected).activate(level, commands, context);
+                    self.active = Some(selected);
+                }
+            }
+        }
+        if let Some(active) = self.active {
+            self.branch(active).step(level, commands, context, info)
+        } else {
+            false
+        }
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(active) = self.active.take() {
+            self.branch(active).deactivate(level, commands, context);
+        }
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(active) = self.active {
+            self.branch(active).suspend(level, commands, context);
+        }
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if let Some(active) = self.active {
+            self.branch(active).resume(level, commands, context);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.active = None;
+        for child in self.children_mut() {
+            child.reset();
+        }
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        if let Some(active) = self.active {
+            self.branch(active).jump_to(label, level, commands, context)
+        } else {
+            false
+        }
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        self.arms
+            .iter()
+            .map(|(_, arm)| arm.as_ref())
+            .chain(self.otherwise.as_deref())
+            .collect()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        self.arms
+            .iter_mut()
+            .map(|(_, arm)| arm)
+            .chain(self.otherwise.as_mut())
+            .collect()
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        match self.active {
+            Some(Branch::Arm(index)) => vec![index],
+            Some(Branch::Otherwise) => vec![self.arms.len()],
+            None => Vec::new(),
+        }
+    }
+}
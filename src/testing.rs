@@ -0,0 +1,106 @@
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+
+use crate::{run_level_plans, LevelContext, LevelPlanElement, StepInfo};
+
+/// Lifecycle calls recorded by `Probe`s, as `"<name> <call>"`.
+#[derive(Clone, Default)]
+pub(crate) struct Log(Arc<Mutex<Vec<String>>>);
+impl Log {
+    pub fn probe(&self, name: &str, steps: usize) -> Probe {
+        Probe {
+            name: name.to_string(),
+            log: self.clone(),
+            steps,
+            remaining: 0,
+        }
+    }
+
+    pub fn push(&self, entry: impl Into<String>) {
+        self.0.lock().unwrap().push(entry.into());
+    }
+
+    /// Returns and clears the recorded calls.
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// Keeps running for `steps` steps after activation and logs every call.
+pub(crate) struct Probe {
+    name: String,
+    log: Log,
+    steps: usize,
+    remaining: usize,
+}
+impl Probe {
+    fn record(&self, call: &str) {
+        self.log.push(format!("{} {}", self.name, call));
+    }
+}
+impl<T> LevelPlanElement<T> for Probe {
+    fn step(&mut self, _: Entity, _: &mut Commands, _: &mut T, _: &StepInfo) -> bool {
+        self.record("step");
+        self.remaining = self.remaining.saturating_sub(1);
+        self.remaining > 0
+    }
+
+    fn activate(&mut self, _: Entity, _: &mut Commands, _: &mut T) {
+        self.record("activate");
+        self.remaining = self.steps;
+    }
+
+    fn deactivate(&mut self, _: Entity, _: &mut Commands, _: &mut T) {
+        self.record("deactivate");
+    }
+
+    fn suspend(&mut self, _: Entity, _: &mut Commands, _: &mut T) {
+        self.record("suspend");
+    }
+
+    fn resume(&mut self, _: Entity, _: &mut Commands, _: &mut T) {
+        self.record("resume");
+    }
+
+    fn reset(&mut self) {
+        self.remaining = 0;
+    }
+
+    fn label(&self) -> String {
+        self.name.clone()
+    }
+}
+
+/// A context with nothing in it, for running plans in a `World`.
+pub(crate) struct Context;
+impl LevelContext for Context {
+    fn build(_world: &World, _resources: &Resources) -> Self {
+        Context
+    }
+}
+
+/// Runs every plan in `world` once, like `level_plan_system` with a 10ms
+/// frame.
+pub(crate) fn run(world: &mut World) {
+    let mut resources = Resources::default();
+    let delta = std::time::Duration::from_millis(10);
+    run_level_plans(world, &mut resources, &mut Context, None, delta);
+}
+
+/// Steps `element` once with a fixed 10ms delta.
+pub(crate) fn step<T>(element: &mut dyn LevelPlanElement<T>, context: &mut T) -> bool {
+    let info = StepInfo {
+        delta: std::time::Duration::from_millis(10),
+        ..Default::default()
+    };
+    element.step(Entity::new(0), &mut Commands::default(), context, &info)
+}
+
+pub(crate) fn activate<T>(element: &mut dyn LevelPlanElement<T>, context: &mut T) {
+    element.activate(Entity::new(0), &mut Commands::default(), context);
+}
+
+pub(crate) fn deactivate<T>(element: &mut dyn LevelPlanElement<T>, context: &mut T) {
+    element.deactivate(Entity::new(0), &mut Commands::default(), context);
+}
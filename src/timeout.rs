@@ -0,0 +1,247 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::{LevelContext, LevelPlanElement, StepInfo, VarKey};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeoutOutcome {
+    Completed,
+    TimedOut,
+}
+
+enum Limit<T> {
+    Progress {
+        measure: Box<dyn Fn(&T) -> f32 + Send + Sync + 'static>,
+        amount: f32,
+        start: f32,
+    },
+    Duration {
+        duration: Duration,
+        elapsed: Duration,
+    },
+}
+impl<T> Limit<T> {
+    fn start(&mut self, context: &T) {
+        match self {
+            Limit::Progress { measure, start, .. } => *start = measure(context),
+            Limit::Duration { elapsed, .. } => *elapsed = Duration::from_secs(0),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            Limit::Progress { start, .. } => *start = 0.0,
+            Limit::Duration { elapsed, .. } => *elapsed = Duration::from_secs(0),
+        }
+    }
+
+    fn expired(&mut self, context: &T, info: &StepInfo) -> bool {
+        match self {
+            Limit::Progress {
+                measure,
+                amount,
+                start,
+            } => measure(context) - *start >= *amount,
+            Limit::Duration { duration, elapsed } => {
+                *elapsed += info.delta;
+                *elapsed >= *duration
+            }
+        }
+    }
+}
+
+/// Runs `element` until it completes or the limit is reached, then runs the
+/// fallback, if any, in its place.
+pub struct Timeout<T> {
+    limit: Limit<T>,
+    element: Box<dyn LevelPlanElement<T>>,
+    element_active: bool,
+    fallback: Option<Box<dyn LevelPlanElement<T>>>,
+    fallback_active: bool,
+    outcome: Option<TimeoutOutcome>,
+    report: Option<VarKey<TimeoutOutcome>>,
+}
+impl<T> Timeout<T> {
+    pub fn new(
+        amount: f32,
+        measure: impl Fn(&T) -> f32 + Send + Sync + 'static,
+        element: impl LevelPlanElement<T> + 'static,
+    ) -> Self {
+        Self::with_limit(
+            Limit::Progress {
+                measure: Box::new(measure),
+                amount,
+                start: 0.0,
+            },
+            element,
+        )
+    }
+
+    pub fn after(duration: Duration, element: impl LevelPlanElement<T> + 'static) -> Self {
+        Self::with_limit(
+            Limit::Duration {
+                duration,
+                elapsed: Duration::from_secs(0),
+            },
+            element,
+        )
+    }
+
+    fn with_limit(limit: Limit<T>, element: impl LevelPlanElement<T> + 'static) -> Self {
+        Self {
+            limit,
+            element: Box::new(element),
+            element_active: false,
+            fallback: None,
+            fallback_active: false,
+            outcome: None,
+            report: None,
+        }
+    }
+
+    pub fn with_fallback(mut self, fallback: impl LevelPlanElement<T> + 'static) -> Self {
+        self.fallback = Some(Box::new(fallback));
+        self
+    }
+
+    pub fn report(mut self, key: VarKey<TimeoutOutcome>) -> Self {
+        self.report = Some(key);
+        self
+    }
+
+    pub fn outcome(&self) -> Option<TimeoutOutcome> {
+        self.outcome
+    }
+}
+impl<T: LevelContext> Timeout<T> {
+    fn finish(&mut self, outcome: TimeoutOutcome, context: &mut T) {
+        self.outcome = Some(outcome);
+        if let (Some(key), Some(blackboard)) = (self.report, context.blackboard_mut()) {
+            blackboard.set(key, outcome);
+        }
+    }
+}
+impl<T: LevelContext> LevelPlanElement<T> for Timeout<T> {
+    fn step(
+        &mut self,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        if self.fallback_active {
+            if let Some(fallback) = self.fallback.as_mut() {
+                return fallback.step(level, commands, context, info);
+            }
+        }
+        if self.outcome.is_some() {
+            false
+        } else if self.limit.expired(context, info) {
+            self.element.deactivate(level, commands, context);
+            self.element_active = false;
+            self.finish(TimeoutOutcome::TimedOut, context);
+            if let Some(fallback) = self.fallback.as_mut() {
+                fallback.activate(level, commands, context);
+                self.fallback_active = true;
+                true
+            } else {
+                false
+            }
+        } else if !self.element.step(level, commands, context, info) {
+            self.finish(TimeoutOutcome::Completed, context);
+            false
+        } else {
+            true
+        }
+    }
+
+    fn activate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        self.outcome = None;
+        self.limit.start(context);
+        self.element.activate(level, commands, context);
+        self.element_active = true;
+    }
+
+    fn deactivate(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if self.element_active {
+            self.element.deactivate(level, commands, context);
+            self.element_active = false;
+        }
+        if self.fallback_active {
+            if let Some(fallback) = self.fallback.as_mut() {
+                fallback.deactivate(level, commands, context);
+            }
+            self.fallback_active = false;
+        }
+    }
+
+    fn suspend(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if self.element_active {
+            self.element.suspend(level, commands, context);
+        } else if let (true, Some(fallback)) = (self.fallback_active, self.fallback.as_mut()) {
+            fallback.suspend(level, commands, context);
+        }
+    }
+
+    fn resume(&mut self, level: Entity, commands: &mut Commands, context: &mut T) {
+        if self.element_active {
+            self.element.resume(level, commands, context);
+        } else if let (true, Some(fallback)) = (self.fallback_active, self.fallback.as_mut()) {
+            fallback.resume(level, commands, context);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.limit.reset();
+        self.element_active = false;
+        self.fallback_active = false;
+        self.outcome = None;
+        for child in self.children_mut() {
+            child.reset();
+        }
+    }
+
+    fn jump_to(
+        &mut self,
+        label: &str,
+        level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+    ) -> bool {
+        if self.element_active {
+            self.element.jump_to(label, level, commands, context)
+        } else if let (true, Some(fallback)) = (self.fallback_active, self.fallback.as_mut()) {
+            fallback.jump_to(label, level, commands, context)
+        } else {
+            false
+        }
+    }
+
+    fn label(&self) -> String {
+        match &self.limit {
+            Limit::Progress { amount, .. } => format!("Timeout({})", amount),
+            Limit::Duration { duration, .. } => format!("Timeout({:?})", duration),
+        }
+    }
+
+    fn children(&self) -> Vec<&dyn LevelPlanElement<T>> {
+        std::iter::once(self.element.as_ref())
+            .chain(self.fallback.as_deref())
+            .collect()
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Box<dyn LevelPlanElement<T>>> {
+        std::iter::once(&mut self.element)
+            .chain(self.fallback.as_mut())
+            .collect()
+    }
+
+    fn active_children(&self) -> Vec<usize> {
+        match (self.element_active, self.fallback_active) {
+            (true, _) => vec![0],
+            (false, true) => vec![1],
+            (false, false) => Vec::new(),
+        }
+    }
+}
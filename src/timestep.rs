@@ -0,0 +1,152 @@
+use std::{marker::PhantomData, time::Duration};
+
+use bevy::prelude::*;
+
+/// Makes `level_plan_system::<T>` step plans on a fixed timestep instead of
+/// once per frame. Frame time is accumulated and every whole step due is run
+/// with a `StepInfo::delta` of exactly `step`, rebuilding the context in
+/// between, so spawn cadence no longer depends on the frame rate.
+///
+/// At most `max_steps` are run per frame; when further behind the backlog is
+/// dropped rather than caught up later. Without bevy's `Time` resource, as in
+/// headless tests, every run of the system performs exactly one step.
+pub struct PlanTimestep<T> {
+    step: Duration,
+    max_steps: usize,
+    accumulated: Duration,
+    _marker: PhantomData<fn() -> T>,
+}
+impl<T> PlanTimestep<T> {
+    /// Panics if `step` is zero.
+    pub fn new(step: Duration) -> Self {
+        assert!(
+            step > Duration::from_secs(0),
+            "PlanTimestep step must not be zero"
+        );
+        Self {
+            step,
+            max_steps: 5,
+            accumulated: Duration::from_secs(0),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Panics if `steps` is zero.
+    pub fn per_second(steps: u32) -> Self {
+        assert!(
+            steps > 0,
+            "PlanTimestep must run at least one step per second"
+        );
+        Self::new(Duration::from_secs(1) / steps)
+    }
+
+    /// Panics if `max_steps` is zero, which would never step the plans.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        assert!(
+            max_steps > 0,
+            "PlanTimestep must run at least one step per frame"
+        );
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// How far into the next step the accumulated time is, from 0 to 1, for
+    /// interpolating between plan steps when rendering.
+    pub fn overstep(&self) -> f32 {
+        self.accumulated.as_secs_f32() / self.step.as_secs_f32()
+    }
+
+    fn advance(&mut self, delta: Duration) -> usize {
+        self.accumulated += delta;
+        let mut steps = 0;
+        while self.accumulated >= self.step && steps < self.max_steps {
+            self.accumulated -= self.step;
+            steps += 1;
+        }
+        if self.accumulated >= self.step {
+            let behind = self.accumulated.as_nanos() % self.step.as_nanos();
+            self.accumulated = Duration::from_nanos(behind as u64);
+        }
+        steps
+    }
+}
+
+/// The deltas to step `LevelPlan<T>`s with this frame: the frame's delta, or
+/// one fixed step for each one due with a `PlanTimestep<T>`.
+pub(crate) fn frame_steps<T: 'static>(resources: &Resources) -> Vec<Duration> {
+    let delta = resources.get::<Time>().map(|time| time.delta);
+    match (resources.get_mut::<PlanTimestep<T>>(), delta) {
+        (Some(mut timestep), Some(delta)) => {
+            let steps = timestep.advance(delta);
+            vec![timestep.step; steps]
+        }
+        (Some(timestep), None) => vec![timestep.step],
+        (None, delta) => vec![delta.unwrap_or_default()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestep() -> PlanTimestep<()> {
+        PlanTimestep::new(Duration::from_millis(10)).max_steps(3)
+    }
+
+    #[test]
+    fn accumulates_partial_steps() {
+        let mut timestep = timestep();
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 1);
+        assert_eq!(timestep.accumulated, Duration::from_millis(2));
+        assert!((timestep.overstep() - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn catches_up_several_steps() {
+        let mut timestep = timestep();
+        assert_eq!(timestep.advance(Duration::from_millis(25)), 2);
+        assert_eq!(timestep.accumulated, Duration::from_millis(5));
+        assert_eq!(timestep.advance(Duration::from_millis(5)), 1);
+        assert_eq!(timestep.accumulated, Duration::from_secs(0));
+    }
+
+    #[test]
+    fn caps_steps_and_drops_the_backlog() {
+        let mut timestep = timestep();
+        assert_eq!(timestep.advance(Duration::from_millis(57)), 3);
+        assert_eq!(timestep.accumulated, Duration::from_millis(7));
+        assert_eq!(timestep.advance(Duration::from_millis(3)), 1);
+    }
+
+    #[test]
+    fn per_second_divides_a_second() {
+        assert_eq!(
+            PlanTimestep::<()>::per_second(50).step(),
+            Duration::from_millis(20)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_zero_step() {
+        PlanTimestep::<()>::new(Duration::from_secs(0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_zero_steps_per_second() {
+        PlanTimestep::<()>::per_second(0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_zero_max_steps() {
+        timestep().max_steps(0);
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    instrument::{child_path, instrument, Hook},
+    LevelContext, LevelPlanElement, StepInfo,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "replay", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlanEventKind {
+    Activate,
+    Step(bool),
+    Deactivate,
+    Suspend,
+    Resume,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlanEvent {
+    pub frame: u64,
+    pub plan: Entity,
+    /// Child indices from the root of the plan, e.g. `/2/0`.
+    pub path: String,
+    pub element: String,
+    pub kind: PlanEventKind,
+    pub context: String,
+}
+
+struct TracerState {
+    frame: u64,
+    events: VecDeque<PlanEvent>,
+    capacity: usize,
+    all_steps: bool,
+}
+
+/// Insert as a resource to have `level_plan_system` record a `PlanEvent`
+/// whenever an element of any plan is activated, completes, is deactivated,
+/// suspended or resumed. With the `tracing` feature every call into an
+/// element also runs inside a span and each event is emitted through
+/// `tracing`.
+///
+/// Clones share the same recorded events.
+#[derive(Clone)]
+pub struct PlanTracer {
+    state: Arc<Mutex<TracerState>>,
+}
+impl Default for PlanTracer {
+    fn default() -> Self {
+        Self::with_capacity(10_000)
+    }
+}
+impl PlanTracer {
+    /// Keeps at most `capacity` events, dropping the oldest first.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TracerState {
+                frame: 0,
+                events: VecDeque::new(),
+                capacity,
+                all_steps: false,
+            })),
+        }
+    }
+
+    /// Records every step rather than only the ones that complete an element.
+    pub fn all_steps(self) -> Self {
+        self.state.lock().unwrap().all_steps = true;
+        self
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.state.lock().unwrap().frame
+    }
+
+    pub fn events(&self) -> Vec<PlanEvent> {
+        self.state.lock().unwrap().events.iter().cloned().collect()
+    }
+
+    pub fn drain(&self) -> Vec<PlanEvent> {
+        self.state.lock().unwrap().events.drain(..).collect()
+    }
+
+    /// How often each element of `plan` was activated, keyed by path, among
+    /// the events still held by the tracer.
+    pub fn visit_counts(&self, plan: Entity) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for event in self.state.lock().unwrap().events.iter() {
+            if event.plan == plan && event.kind == PlanEventKind::Activate {
+                *counts.entry(event.path.clone()).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    pub(crate) fn next_frame(&self) {
+        self.state.lock().unwrap().frame += 1;
+    }
+
+    /// Has every element of the plan on `plan` record its events, the root
+    /// under `path`.
+    pub(crate) fn instrument<T: LevelContext + 'static>(
+        &self,
+        plan: Entity,
+        path: String,
+        root: &mut Box<dyn LevelPlanElement<T>>,
+    ) {
+        let hook = TraceHook {
+            tracer: self.clone(),
+            plan,
+            path,
+        };
+        instrument(hook, root);
+    }
+
+    fn record<T: LevelContext>(
+        &self,
+        hook: &TraceHook,
+        element: &dyn LevelPlanElement<T>,
+        kind: PlanEventKind,
+        context: &T,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        if let (PlanEventKind::Step(true), false) = (&kind, state.all_steps) {
+            return;
+        }
+        let event = PlanEvent {
+            frame: state.frame,
+            plan: hook.plan,
+            path: hook.path.clone(),
+            element: element.label(),
+            kind,
+            context: context.summary(),
+        };
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            frame = event.frame,
+            plan = ?event.plan,
+            path = %event.path,
+            element = %event.element,
+            kind = ?event.kind,
+            context = %event.context,
+            "level plan event"
+        );
+        if state.events.len() >= state.capacity {
+            state.events.pop_front();
+        }
+        state.events.push_back(event);
+    }
+}
+
+#[derive(Clone)]
+struct TraceHook {
+    tracer: PlanTracer,
+    plan: Entity,
+    path: String,
+}
+impl<T: LevelContext + 'static> Hook<T> for TraceHook {
+    fn child(&self, index: usize, _child: &dyn LevelPlanElement<T>) -> Self {
+        Self {
+            path: child_path(&self.path, index),
+            ..self.clone()
+        }
+    }
+
+    fn activate(&self, element: &dyn LevelPlanElement<T>, context: &T) {
+        self.tracer
+            .record(self, element, PlanEventKind::Activate, context);
+    }
+
+    fn step(
+        &self,
+        element: &dyn LevelPlanElement<T>,
+        context: &T,
+        _info: &StepInfo,
+        running: bool,
+    ) {
+        self.tracer
+            .record(self, element, PlanEventKind::Step(running), context);
+    }
+
+    fn deactivate(&self, element: &dyn LevelPlanElement<T>, context: &T) {
+        self.tracer
+            .record(self, element, PlanEventKind::Deactivate, context);
+    }
+
+    fn suspend(&self, element: &dyn LevelPlanElement<T>, context: &T) {
+        self.tracer
+            .record(self, element, PlanEventKind::Suspend, context);
+    }
+
+    fn resume(&self, element: &dyn LevelPlanElement<T>, context: &T) {
+        self.tracer
+            .record(self, element, PlanEventKind::Resume, context);
+    }
+
+    #[cfg(feature = "tracing")]
+    fn span(&self) -> Option<tracing::span::EnteredSpan> {
+        Some(tracing::debug_span!("level_plan_element", path = %self.path).entered())
+    }
+}
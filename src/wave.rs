@@ -0,0 +1,175 @@
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use bevy::{ecs::DynamicBundle, prelude::*};
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{LevelPlanElement, Region, StepInfo};
+
+type Factory<T, B> = Box<dyn Fn(Vec3, &mut T) -> B + Send + Sync + 'static>;
+
+/// Added to every entity spawned by a `SpawnWave`. The wave counts its
+/// members as alive for as long as this component exists, which is why it is
+/// not `Clone`: each member holds the only handle to its own token.
+pub struct WaveMember {
+    _token: Arc<()>,
+}
+
+/// Spawns bundles from `factory` at random points in a region.
+///
+/// With a `count` the wave completes once that many entities have been
+/// spawned and all of them are gone, otherwise it keeps spawning forever.
+/// Deactivating the wave forgets its members but leaves them in the world.
+pub struct SpawnWave<T, B> {
+    region: Region,
+    factory: Factory<T, B>,
+    count: Option<usize>,
+    interval: Duration,
+    max_alive: Option<usize>,
+    rng: StdRng,
+    seed: Option<u64>,
+    spawned: usize,
+    last_spawn: Option<Duration>,
+    members: Vec<Weak<()>>,
+}
+impl<T, B> SpawnWave<T, B> {
+    pub fn new(
+        region: Region,
+        factory: impl Fn(Vec3, &mut T) -> B + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            region,
+            factory: Box::new(factory),
+            count: None,
+            interval: Duration::from_secs(0),
+            max_alive: None,
+            rng: StdRng::from_entropy(),
+            seed: None,
+            spawned: 0,
+            last_spawn: None,
+            members: Vec::new(),
+        }
+    }
+
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    pub fn every(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn max_alive(mut self, max_alive: usize) -> Self {
+        self.max_alive = Some(max_alive);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn alive(&self) -> usize {
+        self.members
+            .iter()
+            .filter(|member| member.strong_count() > 0)
+            .count()
+    }
+
+    pub fn spawned(&self) -> usize {
+        self.spawned
+    }
+
+    fn forget(&mut self) {
+        self.spawned = 0;
+        self.last_spawn = None;
+        self.members.clear();
+    }
+
+    fn can_spawn(&self) -> bool {
+        self.count.is_none_or(|count| self.spawned < count)
+            && self.max_alive.is_none_or(|max| self.alive() < max)
+    }
+}
+impl<T, B: DynamicBundle + Send + Sync + 'static> LevelPlanElement<T> for SpawnWave<T, B> {
+    fn step(
+        &mut self,
+        _level: Entity,
+        commands: &mut Commands,
+        context: &mut T,
+        info: &StepInfo,
+    ) -> bool {
+        let now = info.elapsed;
+        // A clock that went back, as after the plan restarted, makes the
+        // next spawn due straight away.
+        let due = self.last_spawn.is_none_or(|last_spawn| {
+            now.checked_sub(last_spawn)
+                .is_none_or(|since| since >= self.interval)
+        });
+        self.members.retain(|member| member.strong_count() > 0);
+        // Without an interval everything allowed is spawned at once, unless
+        // nothing bounds the wave in which case it is one per step.
+        let bounded = self.count.is_some() || self.max_alive.is_some();
+        let batch = if self.interval == Duration::from_secs(0) && bounded {
+            usize::MAX
+        } else {
+            1
+        };
+        for _ in 0..batch {
+            if !due || !self.can_spawn() {
+                break;
+            }
+            let position = self.region.random_point(&mut self.rng);
+            let token = Arc::new(());
+            self.members.push(Arc::downgrade(&token));
+            commands
+                .spawn((self.factory)(position, context))
+                .with(WaveMember { _token: token });
+            self.spawned += 1;
+            self.last_spawn = Some(now);
+        }
+        match self.count {
+            Some(count) => self.spawned < count || self.alive() > 0,
+            None => true,
+        }
+    }
+
+    fn activate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {
+        self.forget();
+    }
+
+    fn deactivate(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {
+        self.forget();
+    }
+
+    /// Keeps counting the members, and spawns nothing until resumed.
+    fn suspend(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {}
+
+    fn resume(&mut self, _level: Entity, _commands: &mut Commands, _context: &mut T) {}
+
+    fn reset(&mut self) {
+        self.forget();
+        if let Some(seed) = self.seed {
+            self.rng = StdRng::seed_from_u64(seed);
+        }
+    }
+
+    fn label(&self) -> String {
+        let mut parameters = Vec::new();
+        if let Some(count) = self.count {
+            parameters.push(format!("count {}", count));
+        }
+        if self.interval > Duration::from_secs(0) {
+            parameters.push(format!("every {:?}", self.interval));
+        }
+        if let Some(max_alive) = self.max_alive {
+            parameters.push(format!("max alive {}", max_alive));
+        }
+        format!("SpawnWave({})", parameters.join(", "))
+    }
+}